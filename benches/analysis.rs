@@ -0,0 +1,69 @@
+extern crate blackhole_compress;
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::time::{Duration, Instant};
+
+use blackhole_compress::BlackHole;
+use blackhole_compress::options::{AnalysisMethod, BlackHoleOptions};
+
+const ITERATIONS:usize = 3;
+
+fn measure<F>(mut f:F) -> Duration where F: FnMut() {
+    let start = Instant::now();
+
+    for _ in 0..ITERATIONS {
+        f();
+    }
+
+    start.elapsed() / ITERATIONS as u32
+}
+
+fn compare(name:&str,data:&[u8],ngram:&BlackHole,suffix_array:&BlackHole) {
+    let mut size = 16 * 1024;
+
+    loop {
+        let input = &data[..size.min(data.len())];
+
+        let words = suffix_array.analysis_data(input).unwrap().len();
+
+        let ngram_time = measure(|| {
+            ngram.analysis_data(input).unwrap();
+        });
+
+        let suffix_array_time = measure(|| {
+            suffix_array.analysis_data(input).unwrap();
+        });
+
+        println!("{}: {} bytes, words: {}",name,input.len(),words);
+        println!("  n-gram analysis:       {:?}",ngram_time);
+        println!("  suffix array analysis: {:?}",suffix_array_time);
+        println!("  speedup: {:.2}x",ngram_time.as_secs_f64() / suffix_array_time.as_secs_f64());
+
+        if size >= data.len() {
+            break;
+        }
+
+        size *= 4;
+    }
+}
+
+fn main() {
+    let mut reader = BufReader::new(File::open("testdata/legal_moves.rs").unwrap());
+
+    let mut data = Vec::new();
+
+    reader.read_to_end(&mut data).unwrap();
+
+    let ngram = BlackHole::with_options(BlackHoleOptions::builder().analysis_method(AnalysisMethod::NGram).build());
+    let suffix_array = BlackHole::with_options(BlackHoleOptions::builder().analysis_method(AnalysisMethod::SuffixArray).build());
+
+    compare("legal_moves.rs",&data,&ngram,&suffix_array);
+
+    compare("zero run",&vec![0; 256 * 1024],&ngram,&suffix_array);
+
+    let line = b"2024-01-01T00:00:00Z INFO request handled status=200 path=/api/v1/items\n";
+    let log = line.iter().cycle().take(256 * 1024).cloned().collect::<Vec<u8>>();
+
+    compare("repeated log line",&log,&ngram,&suffix_array);
+}
@@ -0,0 +1,74 @@
+extern crate blackhole_compress;
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::ops::Deref;
+use std::time::{Duration, Instant};
+
+use blackhole_compress::BlackHole;
+use blackhole_compress::huffman::HuffmanDecodeTable;
+use blackhole_compress::stream::{StreamReader, StreamWriter};
+
+const ITERATIONS:usize = 10;
+
+fn measure<F>(mut f:F) -> Duration where F: FnMut() {
+    let start = Instant::now();
+
+    for _ in 0..ITERATIONS {
+        f();
+    }
+
+    start.elapsed() / ITERATIONS as u32
+}
+
+fn main() {
+    let mut reader = BufReader::new(File::open("testdata/legal_moves.rs").unwrap());
+
+    let mut data = Vec::new();
+
+    reader.read_to_end(&mut data).unwrap();
+
+    let mut bh = BlackHole::new();
+
+    let words = bh.analysis_data(&data).unwrap();
+    let (seq,tree) = bh.build_words_and_tree(&words,data.len()).unwrap();
+    let seq = seq.into_iter().filter(|w| tree.contains_word(w)).collect::<Vec<Vec<u8>>>();
+
+    let mut encoded = Vec::new();
+
+    {
+        let mut sw = StreamWriter::new(&mut encoded);
+
+        for w in seq.iter() {
+            tree.write(&mut sw,w.clone()).unwrap();
+        }
+
+        sw.pad_zeros().unwrap();
+        sw.flush().unwrap();
+    }
+
+    let table = HuffmanDecodeTable::new(&tree).expect("The code lengths are too long to build a decode table.");
+
+    let tree_time = measure(|| {
+        let mut r = encoded.deref();
+        let mut sr = StreamReader::new(&mut r);
+
+        for _ in 0..seq.len() {
+            tree.find_word(&mut sr).unwrap();
+        }
+    });
+
+    let table_time = measure(|| {
+        let mut r = encoded.deref();
+        let mut sr = StreamReader::new(&mut r);
+
+        for _ in 0..seq.len() {
+            table.find_word(&mut sr).unwrap();
+        }
+    });
+
+    println!("input: {} bytes, tokens: {}, dictionary: {} words",data.len(),seq.len(),tree.len());
+    println!("tree decoding:  {:?}",tree_time);
+    println!("table decoding: {:?}",table_time);
+    println!("speedup: {:.2}x",tree_time.as_secs_f64() / table_time.as_secs_f64());
+}
@@ -0,0 +1,442 @@
+extern crate blackhole_compress;
+
+use std::env;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufReader, BufWriter, IsTerminal, Read, Write};
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+
+use blackhole_compress::BlackHole;
+use blackhole_compress::dictionary::Dictionary;
+use blackhole_compress::format::{FLAG_BLOCKS, FLAG_CONTENT_CHECKSUM, FLAG_DICTIONARY, FLAG_HEADER_CHECKSUM, FLAG_SEEK_INDEX};
+use blackhole_compress::huffman::Bits;
+use blackhole_compress::inspect::StreamInfo;
+use blackhole_compress::options::BlackHoleOptions;
+use blackhole_compress::stream::{StreamReader, StreamWriter};
+
+const EXTENSION:&str = ".bh";
+const DEFAULT_DICTIONARY_FILE:&str = "dictionary.bhd";
+const DEFAULT_DICTIONARY_SIZE:usize = 110 * 1024;
+
+const USAGE:&str = "Usage:
+    blackhole compress [-c] [-k] [-f] [-D DICT] [FILES...]
+    blackhole decompress [-c] [-k] [-f] [-D DICT] [FILES...]
+    blackhole inspect [-D DICT] [FILES...]
+    blackhole train [-o FILE] [--max-size BYTES] [--min-count N] FILES...
+
+Commands:
+    compress      Compress FILES into FILE.bh (stdin to stdout when no file or '-' is given)
+    decompress    Decompress FILE.bh into FILE (stdin to stdout when no file or '-' is given)
+    inspect       Print the header, dictionary and Huffman codes of compressed FILES (alias: info)
+    train         Train a shared dictionary from sample files
+
+Compress and decompress options:
+    -c, --stdout            Write to stdout and keep the input files
+    -k, --keep              Keep the input files
+    -f, --force             Overwrite existing output files and allow compressed output to a terminal
+    -D, --dictionary FILE   Use the shared dictionary in FILE
+
+Train options:
+    -o, --output FILE       Write the dictionary to FILE (default: dictionary.bhd)
+    -s, --max-size BYTES    Upper bound of the dictionary size in bytes (default: 112640)
+    -m, --min-count N       Minimum number of occurrences of a dictionary word (default: 8)";
+
+fn parse_number(name:&str,value:Option<String>) -> Result<usize,String> {
+    let value = value.ok_or_else(|| format!("option '{}' requires a value",name))?;
+
+    value.parse().map_err(|_| format!("invalid value for option '{}': {}",name,value))
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+enum Mode {
+    Compress,
+    Decompress
+}
+struct CodecArgs {
+    stdout:bool,
+    keep:bool,
+    force:bool,
+    dictionary:Option<String>,
+    files:Vec<String>
+}
+fn parse_codec_args<I>(mut args:I) -> Result<CodecArgs,String> where I: Iterator<Item=String> {
+    let mut r = CodecArgs {
+        stdout: false,
+        keep: false,
+        force: false,
+        dictionary: None,
+        files: Vec::new()
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-c" | "--stdout" => r.stdout = true,
+            "-k" | "--keep" => r.keep = true,
+            "-f" | "--force" => r.force = true,
+            "-D" | "--dictionary" => {
+                r.dictionary = Some(args.next().ok_or_else(|| format!("option '{}' requires a value",arg))?);
+            },
+            "--" => {
+                r.files.extend(&mut args);
+            },
+            _ if arg.starts_with("--") => {
+                return Err(format!("unknown option '{}'",arg));
+            },
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                for c in arg[1..].chars() {
+                    match c {
+                        'c' => r.stdout = true,
+                        'k' => r.keep = true,
+                        'f' => r.force = true,
+                        _ => {
+                            return Err(format!("unknown option '-{}'",c));
+                        }
+                    }
+                }
+            },
+            _ => {
+                r.files.push(arg);
+            }
+        }
+    }
+
+    if r.files.is_empty() {
+        r.files.push(String::from("-"));
+    }
+
+    Ok(r)
+}
+
+fn options(dictionary:Option<&String>) -> Result<BlackHoleOptions,String> {
+    let mut builder = BlackHoleOptions::builder();
+
+    if let Some(path) = dictionary {
+        let bytes = fs::read(path).map_err(|e| format!("{}: {}",path,e))?;
+        let dictionary = Dictionary::from_bytes(&bytes).map_err(|e| format!("{}: {}",path,e))?;
+
+        builder = builder.dictionary(Arc::new(dictionary));
+    }
+
+    Ok(builder.build())
+}
+
+fn transcode<R,W>(mode:Mode,options:&BlackHoleOptions,reader:&mut R,writer:&mut W) -> Result<(),String> where R: Read, W: Write {
+    let mut bh = BlackHole::with_options(options.clone());
+
+    {
+        let mut sr = StreamReader::new(reader);
+        let mut sw = StreamWriter::new(writer);
+
+        match mode {
+            Mode::Compress => {
+                bh.compression(&mut sr,&mut sw).map_err(|e| e.to_string())?;
+            },
+            Mode::Decompress => {
+                bh.uncompression(&mut sr,&mut sw).map_err(|e| e.to_string())?;
+            }
+        }
+
+        sw.flush().map_err(|e| e.to_string())?;
+    }
+
+    writer.flush().map_err(|e| e.to_string())
+}
+
+fn output_path(mode:Mode,input:&str) -> Result<String,String> {
+    match mode {
+        Mode::Compress if input.ends_with(EXTENSION) => {
+            Err(format!("{}: already has {} suffix",input,EXTENSION))
+        },
+        Mode::Compress => Ok(format!("{}{}",input,EXTENSION)),
+        Mode::Decompress => {
+            match input.strip_suffix(EXTENSION) {
+                Some(output) if !output.is_empty() => Ok(String::from(output)),
+                _ => Err(format!("{}: unknown suffix, expected {}",input,EXTENSION))
+            }
+        }
+    }
+}
+
+fn process_file(mode:Mode,args:&CodecArgs,options:&BlackHoleOptions,input:&str) -> Result<(),String> {
+    let stdout = io::stdout();
+
+    if input == "-" {
+        if mode == Mode::Compress && !args.force && stdout.is_terminal() {
+            return Err(String::from("refusing to write compressed data to a terminal (use -f to force)"));
+        }
+
+        let stdin = io::stdin();
+
+        return transcode(mode,options,&mut stdin.lock(),&mut BufWriter::new(stdout.lock()));
+    }
+
+    if !Path::new(input).is_file() {
+        return Err(format!("{}: no such file",input));
+    }
+
+    let mut reader = BufReader::new(File::open(input).map_err(|e| format!("{}: {}",input,e))?);
+
+    if args.stdout {
+        if mode == Mode::Compress && !args.force && stdout.is_terminal() {
+            return Err(String::from("refusing to write compressed data to a terminal (use -f to force)"));
+        }
+
+        return transcode(mode,options,&mut reader,&mut BufWriter::new(stdout.lock())).map_err(|e| format!("{}: {}",input,e));
+    }
+
+    let output = output_path(mode,input)?;
+
+    let file = if args.force {
+        File::create(&output)
+    } else {
+        OpenOptions::new().write(true).create_new(true).open(&output)
+    }.map_err(|e| format!("{}: {}",output,e))?;
+
+    if let Err(e) = transcode(mode,options,&mut reader,&mut BufWriter::new(file)) {
+        let _ = fs::remove_file(&output);
+
+        return Err(format!("{}: {}",input,e));
+    }
+
+    if !args.keep {
+        fs::remove_file(input).map_err(|e| format!("{}: {}",input,e))?;
+    }
+
+    Ok(())
+}
+
+fn codec<I>(mode:Mode,args:I) -> Result<(),String> where I: Iterator<Item=String> {
+    let args = parse_codec_args(args)?;
+    let options = options(args.dictionary.as_ref())?;
+
+    let mut failed = false;
+
+    for input in args.files.iter() {
+        if let Err(e) = process_file(mode,&args,&options,input) {
+            eprintln!("blackhole: {}",e);
+            failed = true;
+        }
+    }
+
+    if failed {
+        Err(String::from("some files could not be processed"))
+    } else {
+        Ok(())
+    }
+}
+
+fn escape(word:&[u8]) -> String {
+    word.iter().flat_map(|&b| std::ascii::escape_default(b)).map(|b| b as char).collect()
+}
+
+fn code(bits:&Bits) -> String {
+    (0..bits.len()).map(|i| if bits.get_bit(i).unwrap_or(0) == 1 { '1' } else { '0' }).collect()
+}
+
+fn percentage(n:u64,total:u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        n as f64 * 100.0 / total as f64
+    }
+}
+
+fn print_info<W>(out:&mut W,name:&str,info:&StreamInfo) -> io::Result<()> where W: Write {
+    let header = info.header();
+
+    let flags = [(FLAG_CONTENT_CHECKSUM,"content-checksum"),
+                 (FLAG_HEADER_CHECKSUM,"header-checksum"),
+                 (FLAG_BLOCKS,"blocks"),
+                 (FLAG_SEEK_INDEX,"seek-index"),
+                 (FLAG_DICTIONARY,"dictionary")].iter()
+                                                .filter(|&&(f,_)| header.has_flag(f))
+                                                .map(|&(_,n)| n)
+                                                .collect::<Vec<&str>>();
+
+    let total = info.compressed_size();
+
+    writeln!(out,"{}:",name)?;
+    writeln!(out,"  version:           {}",header.version())?;
+    writeln!(out,"  flags:             {}",if flags.is_empty() { String::from("none") } else { flags.join(", ") })?;
+    writeln!(out,"  original size:     {} bytes",info.size())?;
+    writeln!(out,"  compressed size:   {} bytes ({:.2}%)",total,percentage(total,info.size()))?;
+    writeln!(out,"  header:            {} bytes ({:.2}%)",info.header_size(),percentage(info.header_size() as u64,total))?;
+    writeln!(out,"  block framing:     {} bytes ({:.2}%)",info.framing_size(),percentage(info.framing_size() as u64,total))?;
+    writeln!(out,"  dictionary:        {} bytes ({:.2}%)",info.dictionary_size(),percentage(info.dictionary_size() as u64,total))?;
+    writeln!(out,"  payload:           {} bytes ({:.2}%)",info.payload_size(),percentage(info.payload_size() as u64,total))?;
+    writeln!(out,"  seek index:        {} bytes ({:.2}%)",info.index_size(),percentage(info.index_size() as u64,total))?;
+    writeln!(out,"  literal tokens:    {} ({} bytes)",info.literal_tokens(),info.literal_tokens())?;
+    writeln!(out,"  dictionary tokens: {} ({} bytes)",info.word_tokens(),info.word_bytes())?;
+
+    for (i,block) in info.blocks().iter().enumerate() {
+        writeln!(out)?;
+        if block.is_stored() {
+            writeln!(out,"  block {}: {} -> {} bytes (stored)",i,block.size(),block.compressed_size())?;
+
+            continue;
+        }
+
+        writeln!(out,"  block {}: {} -> {} bytes (dictionary {} bytes, payload {} bytes)",
+                 i,block.size(),block.compressed_size(),block.dictionary_size(),block.payload_size())?;
+        writeln!(out,"    tokens: {} literal, {} dictionary ({} bytes)",block.literal_tokens(),block.word_tokens(),block.word_bytes())?;
+
+        if let Some(id) = block.shared_dictionary() {
+            writeln!(out,"    shared dictionary: {:08x}",id)?;
+        }
+
+        writeln!(out,"    dictionary entries: {}",block.words().len())?;
+
+        let mut words = block.words().iter().collect::<Vec<&(Vec<u8>,Bits)>>();
+
+        words.sort_by(|(lw,lb),(rw,rb)| lb.len().cmp(&rb.len()).then(lw.cmp(rw)));
+
+        for (word,bits) in words {
+            writeln!(out,"    {:>4} {:<24} \"{}\"",bits.len(),code(bits),escape(word))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn inspect<I>(mut args:I) -> Result<(),String> where I: Iterator<Item=String> {
+    let mut dictionary = None;
+    let mut files = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-D" | "--dictionary" => {
+                dictionary = Some(args.next().ok_or_else(|| format!("option '{}' requires a value",arg))?);
+            },
+            "--" => {
+                files.extend(&mut args);
+            },
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(format!("unknown option '{}'",arg));
+            },
+            _ => {
+                files.push(arg);
+            }
+        }
+    }
+
+    if files.is_empty() {
+        files.push(String::from("-"));
+    }
+
+    let options = options(dictionary.as_ref())?;
+
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+
+    let mut failed = false;
+
+    for file in files.iter() {
+        let mut reader: Box<dyn Read> = if file == "-" {
+            Box::new(io::stdin())
+        } else {
+            match File::open(file) {
+                Ok(f) => Box::new(BufReader::new(f)),
+                Err(e) => {
+                    eprintln!("blackhole: {}: {}",file,e);
+                    failed = true;
+                    continue;
+                }
+            }
+        };
+
+        let mut sr = StreamReader::new(&mut reader);
+
+        match BlackHole::with_options(options.clone()).inspect(&mut sr) {
+            Ok(info) => {
+                print_info(&mut out,file,&info).map_err(|e| e.to_string())?;
+            },
+            Err(e) => {
+                eprintln!("blackhole: {}: {}",file,e);
+                failed = true;
+            }
+        }
+    }
+
+    out.flush().map_err(|e| e.to_string())?;
+
+    if failed {
+        Err(String::from("some files could not be inspected"))
+    } else {
+        Ok(())
+    }
+}
+
+fn train<I>(mut args:I) -> Result<(),String> where I: Iterator<Item=String> {
+    let mut output = String::from(DEFAULT_DICTIONARY_FILE);
+    let mut max_size = DEFAULT_DICTIONARY_SIZE;
+    let mut min_count = BlackHoleOptions::new().min_count();
+    let mut files = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => {
+                output = args.next().ok_or_else(|| format!("option '{}' requires a value",arg))?;
+            },
+            "-s" | "--max-size" => {
+                max_size = parse_number(&arg,args.next())?;
+            },
+            "-m" | "--min-count" => {
+                min_count = parse_number(&arg,args.next())?;
+            },
+            "--" => {
+                files.extend(&mut args);
+            },
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(format!("unknown option '{}'",arg));
+            },
+            _ => {
+                files.push(arg);
+            }
+        }
+    }
+
+    if files.is_empty() {
+        return Err(String::from("no sample files were given"));
+    }
+
+    let mut samples = Vec::new();
+
+    for file in files.iter() {
+        samples.push(fs::read(file).map_err(|e| format!("{}: {}",file,e))?);
+    }
+
+    let options = BlackHoleOptions::builder().min_count(min_count).build();
+    let dictionary = Dictionary::train_with_max_size(&samples,&options,max_size).map_err(|e| e.to_string())?;
+    let bytes = dictionary.to_bytes().map_err(|e| e.to_string())?;
+
+    File::create(&output).and_then(|mut f| f.write_all(&bytes)).map_err(|e| format!("{}: {}",output,e))?;
+
+    eprintln!("{}: {} words, {} bytes, id {:08x}",output,dictionary.len(),bytes.len(),dictionary.id());
+
+    Ok(())
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+
+    let r = match args.next().as_deref() {
+        Some("compress") => codec(Mode::Compress,args),
+        Some("decompress") => codec(Mode::Decompress,args),
+        Some("inspect") | Some("info") => inspect(args),
+        Some("train") => train(args),
+        Some("-h") | Some("--help") | Some("help") => {
+            println!("{}",USAGE);
+            Ok(())
+        },
+        Some(command) => Err(format!("unknown command '{}'\n\n{}",command,USAGE)),
+        None => Err(String::from(USAGE))
+    };
+
+    if let Err(e) = r {
+        eprintln!("blackhole: {}",e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,64 @@
+const fn build_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB88320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+
+            k += 1;
+        }
+
+        table[i] = c;
+        i += 1;
+    }
+
+    table
+}
+
+const TABLE:[u32; 256] = build_table();
+
+#[derive(Debug,Clone,Copy)]
+pub struct Crc32 {
+    crc:u32
+}
+impl Crc32 {
+    pub fn new() -> Crc32 {
+        Crc32 {
+            crc: 0xFFFFFFFF
+        }
+    }
+
+    pub fn update(&mut self,bytes:&[u8]) {
+        let mut crc = self.crc;
+
+        for &b in bytes {
+            crc = TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8);
+        }
+
+        self.crc = crc;
+    }
+
+    pub fn value(&self) -> u32 {
+        self.crc ^ 0xFFFFFFFF
+    }
+}
+impl Default for Crc32 {
+    fn default() -> Self {
+        Crc32::new()
+    }
+}
+pub fn crc32(bytes:&[u8]) -> u32 {
+    let mut crc = Crc32::new();
+
+    crc.update(bytes);
+
+    crc.value()
+}
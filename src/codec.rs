@@ -0,0 +1,538 @@
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::Deref;
+
+use crate::{BlackHole, DataDecoder};
+use crate::error::{CompressionError, UnCompressionError};
+use crate::format::{BLOCK_END, BlockHeader, BlockIndex, FLAG_BLOCKS, FLAG_SEEK_INDEX, FOOTER_SIZE, Header, HEADER_SIZE, INDEX_ENTRY_SIZE, read_footer, SIZE_UNKNOWN, size_len};
+use crate::inspect::BlockInfo;
+use crate::options::{BlackHoleOptions, DEFAULT_BLOCK_SIZE};
+use crate::stream::{StreamReader, StreamReaderState, StreamWriter};
+
+const DECODE_CHUNK_SIZE:usize = 64 * 1024;
+
+fn compression_error(e:CompressionError) -> io::Error {
+    io::Error::other(e)
+}
+fn finished_error() -> io::Error {
+    io::Error::other("The encoder has already been finished.")
+}
+fn uncompression_error(e:UnCompressionError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+fn read_full<R>(reader:&mut R,buf:&mut [u8]) -> io::Result<usize> where R: Read {
+    let mut size = 0;
+
+    while size < buf.len() {
+        let n = reader.read(&mut buf[size..])?;
+
+        if n == 0 {
+            break;
+        }
+
+        size += n;
+    }
+
+    Ok(size)
+}
+fn read_header<R>(reader:&mut R) -> io::Result<Header> where R: Read {
+    let mut buf = [0; HEADER_SIZE];
+
+    let size = read_full(reader,&mut buf)?;
+
+    let mut r = &buf[..size];
+    let mut sr = StreamReader::new(&mut r);
+
+    Header::read(&mut sr).map_err(uncompression_error)
+}
+fn read_block_header<R>(reader:&mut R) -> io::Result<BlockHeader> where R: Read {
+    let mut buf = vec![0];
+
+    reader.read_exact(&mut buf)?;
+
+    if buf[0] != BLOCK_END {
+        for _ in 0..2 {
+            let start = buf.len();
+
+            buf.push(0);
+
+            reader.read_exact(&mut buf[start..])?;
+
+            let len = size_len(buf[start]);
+
+            buf.resize(start + len, 0);
+
+            reader.read_exact(&mut buf[(start + 1)..])?;
+        }
+    }
+
+    let mut r = buf.deref();
+    let mut sr = StreamReader::new(&mut r);
+
+    BlockHeader::read(&mut sr).map_err(uncompression_error)
+}
+fn read_block<R>(reader:&mut R,blackhole:&mut BlackHole,header:&Header,block_header:&BlockHeader,buf:&mut Vec<u8>) -> io::Result<()> where R: Read {
+    let mut body = Vec::new();
+
+    reader.take(block_header.compressed_size() as u64).read_to_end(&mut body)?;
+
+    if body.len() < block_header.compressed_size() {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+    }
+
+    let mut r = body.deref();
+    let mut sr = StreamReader::new(&mut r);
+    let mut sw = StreamWriter::new(buf);
+
+    blackhole.uncompress_block(&mut sr,&mut sw,header,block_header).map_err(uncompression_error)?;
+    sw.flush().map_err(|e| uncompression_error(UnCompressionError::from(e)))
+}
+pub struct BlackHoleEncoder<W> where W: Write {
+    writer:Option<W>,
+    blackhole:BlackHole,
+    buf:Vec<u8>,
+    block_size:usize,
+    header_written:bool,
+    index:BlockIndex,
+    offset:u64,
+    finished:bool
+}
+impl<W> BlackHoleEncoder<W> where W: Write {
+    pub fn new(writer:W) -> BlackHoleEncoder<W> {
+        BlackHoleEncoder::with_options(writer,BlackHoleOptions::new())
+    }
+
+    pub fn with_options(writer:W,options:BlackHoleOptions) -> BlackHoleEncoder<W> {
+        let block_size = options.block_size().filter(|&s| s > 0).unwrap_or(DEFAULT_BLOCK_SIZE);
+
+        BlackHoleEncoder {
+            writer: Some(writer),
+            blackhole: BlackHole::with_options(options),
+            buf: Vec::new(),
+            block_size,
+            header_written: false,
+            index: BlockIndex::new(),
+            offset: HEADER_SIZE as u64,
+            finished: false
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.writer.as_ref().expect("The encoder has already been finished.")
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        self.writer.as_mut().expect("The encoder has already been finished.")
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        if !self.header_written {
+            let flags = self.blackhole.flags() | FLAG_BLOCKS;
+            let writer = self.writer.as_mut().ok_or_else(finished_error)?;
+
+            let mut sw = StreamWriter::new(writer);
+
+            Header::new(self.blackhole.options().version(),flags,SIZE_UNKNOWN).write(&mut sw).map_err(|e| compression_error(CompressionError::from(e)))?;
+            sw.flush().map_err(|e| compression_error(CompressionError::from(e)))?;
+
+            self.header_written = true;
+        }
+
+        Ok(())
+    }
+
+    fn write_block(&mut self,size:usize) -> io::Result<()> {
+        if size == 0 {
+            return Ok(());
+        }
+
+        self.write_header()?;
+
+        let data = self.buf.drain(..size).collect::<Vec<u8>>();
+        let writer = self.writer.as_mut().ok_or_else(finished_error)?;
+
+        let mut sw = StreamWriter::new(writer);
+
+        self.index.push(self.offset,data.len() as u64);
+
+        self.offset += self.blackhole.compress_block(&mut sw,&data).map_err(compression_error)? as u64;
+
+        Ok(())
+    }
+
+    fn try_finish(&mut self) -> io::Result<()> {
+        let size = self.buf.len();
+
+        self.finished = true;
+
+        self.write_block(size)?;
+        self.write_header()?;
+
+        if let Some(writer) = self.writer.as_mut() {
+            let mut sw = StreamWriter::new(writer);
+
+            self.blackhole.finish_blocks(&mut sw,&self.index,self.offset).map_err(compression_error)?;
+
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.try_finish()?;
+
+        Ok(self.writer.take().expect("The encoder has already been finished."))
+    }
+}
+impl<W> Write for BlackHoleEncoder<W> where W: Write {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.finished {
+            return Err(finished_error());
+        }
+
+        self.buf.extend_from_slice(buf);
+
+        while self.buf.len() >= self.block_size {
+            let size = self.block_size;
+
+            self.write_block(size)?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let size = self.buf.len();
+
+        self.write_block(size)?;
+
+        if let Some(writer) = self.writer.as_mut() {
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+}
+impl<W> Drop for BlackHoleEncoder<W> where W: Write {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.try_finish();
+        }
+    }
+}
+pub struct BlackHoleDecoder<R> where R: Read {
+    reader:R,
+    blackhole:BlackHole,
+    header:Option<Header>,
+    buf:Vec<u8>,
+    current_index:usize,
+    total_size:u64,
+    reader_state:Option<StreamReaderState>,
+    data:Option<DataDecoder>,
+    finished:bool
+}
+impl<R> BlackHoleDecoder<R> where R: Read {
+    pub fn new(reader:R) -> BlackHoleDecoder<R> {
+        BlackHoleDecoder::with_options(reader,BlackHoleOptions::new())
+    }
+
+    pub fn with_options(reader:R,options:BlackHoleOptions) -> BlackHoleDecoder<R> {
+        BlackHoleDecoder {
+            reader,
+            blackhole: BlackHole::with_options(options),
+            header: None,
+            buf: Vec::new(),
+            current_index: 0,
+            total_size: 0,
+            reader_state: None,
+            data: None,
+            finished: false
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_index(&mut self) -> io::Result<()> {
+        let mut count = [0; 8];
+
+        self.reader.read_exact(&mut count)?;
+
+        let size = u64::from_le_bytes(count).checked_mul(INDEX_ENTRY_SIZE as u64)
+                                           .and_then(|s| s.checked_add(FOOTER_SIZE as u64))
+                                           .ok_or_else(|| uncompression_error(UnCompressionError::FormatError))?;
+
+        let mut buf = count.to_vec();
+
+        (&mut self.reader).take(size).read_to_end(&mut buf)?;
+
+        let mut r = buf.deref();
+        let mut sr = StreamReader::new(&mut r);
+
+        let (index,_) = BlockIndex::read(&mut sr).map_err(uncompression_error)?;
+
+        if index.total_size() != self.total_size {
+            return Err(uncompression_error(UnCompressionError::FormatError));
+        }
+
+        Ok(())
+    }
+
+    fn fill_buf(&mut self) -> io::Result<()> {
+        let header = match self.header {
+            Some(header) => header,
+            None => {
+                let header = read_header(&mut self.reader)?;
+
+                self.blackhole.check_output_size(header.size()).map_err(uncompression_error)?;
+
+                self.header = Some(header);
+
+                header
+            }
+        };
+
+        self.buf.clear();
+        self.current_index = 0;
+
+        if !header.has_flag(FLAG_BLOCKS) {
+            // Streams without blocks are decoded a chunk at a time, keeping the dictionary and the bit reader between calls.
+            // They have no framing to bound reads, so the bit reader may take bytes past the end of the stream from the inner reader.
+            let mut sr = match self.reader_state.take() {
+                Some(state) => StreamReader::with_state(&mut self.reader,state),
+                None => StreamReader::new(&mut self.reader)
+            };
+            let mut sw = StreamWriter::new(&mut self.buf);
+            let mut info = BlockInfo::new();
+
+            let mut data = match self.data.take() {
+                Some(data) => data,
+                None => self.blackhole.begin_data(&mut sr,&header,header.size() as usize,&mut info).map_err(uncompression_error)?
+            };
+
+            self.blackhole.decode_data(&mut data,&mut sr,&mut sw,&mut info,DECODE_CHUNK_SIZE).map_err(uncompression_error)?;
+            sw.flush().map_err(|e| uncompression_error(UnCompressionError::from(e)))?;
+
+            self.finished = data.is_finished();
+
+            if !self.finished {
+                self.reader_state = Some(sr.into_state());
+                self.data = Some(data);
+            }
+
+            return Ok(());
+        }
+
+        let block_header = read_block_header(&mut self.reader)?;
+
+        if block_header.is_end() {
+            self.finished = true;
+
+            if header.size() != SIZE_UNKNOWN && header.size() != self.total_size {
+                return Err(uncompression_error(UnCompressionError::FormatError));
+            }
+
+            if header.has_flag(FLAG_SEEK_INDEX) {
+                self.read_index()?;
+            }
+
+            return Ok(());
+        }
+
+        self.blackhole.check_output_size(self.total_size + block_header.size() as u64).map_err(uncompression_error)?;
+
+        read_block(&mut self.reader,&mut self.blackhole,&header,&block_header,&mut self.buf)?;
+
+        self.total_size += block_header.size() as u64;
+
+        Ok(())
+    }
+}
+impl<R> Read for BlackHoleDecoder<R> where R: Read {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.current_index >= self.buf.len() {
+            if self.finished {
+                return Ok(0);
+            }
+
+            self.fill_buf()?;
+        }
+
+        let size = buf.len().min(self.buf.len() - self.current_index);
+
+        buf[..size].copy_from_slice(&self.buf[self.current_index..(self.current_index + size)]);
+
+        self.current_index += size;
+
+        Ok(size)
+    }
+}
+pub struct BlackHoleSeekableReader<R> where R: Read + Seek {
+    reader:R,
+    blackhole:BlackHole,
+    header:Header,
+    index:BlockIndex,
+    position:u64,
+    current_block:Option<usize>,
+    buf:Vec<u8>
+}
+impl<R> BlackHoleSeekableReader<R> where R: Read + Seek {
+    pub fn new(reader:R) -> io::Result<BlackHoleSeekableReader<R>> {
+        BlackHoleSeekableReader::with_options(reader,BlackHoleOptions::new())
+    }
+
+    pub fn with_options(mut reader:R,options:BlackHoleOptions) -> io::Result<BlackHoleSeekableReader<R>> {
+        reader.seek(SeekFrom::Start(0))?;
+
+        let header = read_header(&mut reader)?;
+
+        if !header.has_flag(FLAG_SEEK_INDEX) {
+            return Err(uncompression_error(UnCompressionError::InvalidState(String::from("The stream does not have a seek index."))));
+        }
+
+        let end = reader.seek(SeekFrom::End(-(FOOTER_SIZE as i64)))?;
+
+        let index_offset = {
+            let mut sr = StreamReader::new(&mut reader);
+
+            read_footer(&mut sr).map_err(uncompression_error)?
+        };
+
+        if index_offset < HEADER_SIZE as u64 || index_offset > end {
+            return Err(uncompression_error(UnCompressionError::FormatError));
+        }
+
+        reader.seek(SeekFrom::Start(index_offset))?;
+
+        let (index,offset) = {
+            let mut r = (&mut reader).take(end - index_offset + FOOTER_SIZE as u64);
+            let mut sr = StreamReader::new(&mut r);
+
+            BlockIndex::read(&mut sr).map_err(uncompression_error)?
+        };
+
+        if offset != index_offset || index.entries().iter().any(|e| e.compressed_offset() >= index_offset) {
+            return Err(uncompression_error(UnCompressionError::FormatError));
+        }
+
+        if header.size() != SIZE_UNKNOWN && header.size() != index.total_size() {
+            return Err(uncompression_error(UnCompressionError::FormatError));
+        }
+
+        Ok(BlackHoleSeekableReader {
+            reader,
+            blackhole: BlackHole::with_options(options),
+            header,
+            index,
+            position: 0,
+            current_block: None,
+            buf: Vec::new()
+        })
+    }
+
+    pub fn index(&self) -> &BlockIndex {
+        &self.index
+    }
+
+    pub fn len(&self) -> u64 {
+        self.index.total_size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn load_block(&mut self,block:usize) -> io::Result<()> {
+        if self.current_block == Some(block) {
+            return Ok(());
+        }
+
+        let entry = self.index.entries()[block];
+
+        self.current_block = None;
+        self.buf.clear();
+
+        self.blackhole.check_output_size(entry.size()).map_err(uncompression_error)?;
+
+        self.reader.seek(SeekFrom::Start(entry.compressed_offset()))?;
+
+        let block_header = read_block_header(&mut self.reader)?;
+
+        if block_header.is_end() || block_header.size() as u64 != entry.size() {
+            return Err(uncompression_error(UnCompressionError::FormatError));
+        }
+
+        read_block(&mut self.reader,&mut self.blackhole,&self.header,&block_header,&mut self.buf)?;
+
+        self.current_block = Some(block);
+
+        Ok(())
+    }
+}
+impl<R> Read for BlackHoleSeekableReader<R> where R: Read + Seek {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let block = match self.index.find(self.position) {
+            Some(block) => block,
+            None => {
+                return Ok(0);
+            }
+        };
+
+        self.load_block(block)?;
+
+        let start = (self.position - self.index.entries()[block].uncompressed_offset()) as usize;
+        let size = buf.len().min(self.buf.len() - start);
+
+        buf[..size].copy_from_slice(&self.buf[start..(start + size)]);
+
+        self.position += size as u64;
+
+        Ok(size)
+    }
+}
+impl<R> Seek for BlackHoleSeekableReader<R> where R: Read + Seek {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base,offset) = match pos {
+            SeekFrom::Start(n) => {
+                self.position = n;
+
+                return Ok(n);
+            },
+            SeekFrom::End(n) => (self.len(),n),
+            SeekFrom::Current(n) => (self.position,n)
+        };
+
+        match base.checked_add_signed(offset) {
+            Some(position) => {
+                self.position = position;
+
+                Ok(position)
+            },
+            None => {
+                Err(io::Error::new(io::ErrorKind::InvalidInput,"invalid seek to a negative or overflowing position"))
+            }
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{BlackHole, Score, Word};
+use crate::checksum::crc32;
+use crate::error::{CompressionError, UnCompressionError};
+use crate::format::{DICTIONARY_MAGIC, encoded_size_len, read_size, write_size};
+use crate::huffman::{HuffmanDecodeTable, HuffmanTree};
+use crate::options::BlackHoleOptions;
+use crate::stream::{StreamReader, StreamWriter};
+
+#[derive(Debug)]
+pub struct Dictionary {
+    id:u32,
+    words:BTreeMap<Vec<u8>,usize>,
+    max_word_size:usize,
+    tree:HuffmanTree<Vec<u8>>,
+    table:Option<HuffmanDecodeTable<Vec<u8>>>,
+    unified_tree:HuffmanTree<Vec<u8>>,
+    unified_table:Option<HuffmanDecodeTable<Vec<u8>>>
+}
+impl Dictionary {
+    pub fn new(words:BTreeMap<Vec<u8>,usize>) -> Result<Dictionary,CompressionError> {
+        let id = crc32(&Dictionary::encode_entries(&words)?);
+        let max_word_size = words.keys().map(|w| w.len()).max().unwrap_or(0);
+
+        let tree = HuffmanTree::canonical(words.iter().map(|(w,&c)| (w.clone(),Score::new(w.len(),c))).collect())?;
+        let table = HuffmanDecodeTable::new(&tree);
+
+        let literals = (0..=255u8).map(|b| vec![b]).filter(|w| !words.contains_key(w)).map(|w| (w,Score::new(1,1)));
+
+        let unified_tree = HuffmanTree::canonical(words.iter().map(|(w,&c)| (w.clone(),Score::new(w.len(),c))).chain(literals).collect())?;
+        let unified_table = HuffmanDecodeTable::new(&unified_tree);
+
+        Ok(Dictionary {
+            id,
+            words,
+            max_word_size,
+            tree,
+            table,
+            unified_tree,
+            unified_table
+        })
+    }
+
+    pub fn train<S>(samples:&[S],options:&BlackHoleOptions) -> Result<Dictionary,CompressionError> where S: AsRef<[u8]> {
+        Dictionary::new(Dictionary::statistics(samples,options)?)
+    }
+
+    pub fn train_with_max_size<S>(samples:&[S],options:&BlackHoleOptions,max_size:usize) -> Result<Dictionary,CompressionError> where S: AsRef<[u8]> {
+        Dictionary::from_statistics(&Dictionary::statistics(samples,options)?,max_size,options.min_count())
+    }
+
+    pub fn statistics<S>(samples:&[S],options:&BlackHoleOptions) -> Result<BTreeMap<Vec<u8>,usize>,CompressionError> where S: AsRef<[u8]> {
+        let bh = BlackHole::with_options(options.clone());
+
+        let mut counts = BTreeMap::new();
+
+        for sample in samples.iter() {
+            let data = sample.as_ref();
+
+            let words = bh.analysis_data(data)?;
+            let (seq,_) = bh.select_words(&words,data.len());
+
+            for w in seq.into_iter() {
+                *counts.entry(w).or_insert(0) += 1;
+            }
+        }
+
+        Ok(counts)
+    }
+
+    pub fn from_statistics(statistics:&BTreeMap<Vec<u8>,usize>,max_size:usize,min_count:usize) -> Result<Dictionary,CompressionError> {
+        let entry_size = |word:&[u8],count:usize| encoded_size_len(word.len()) + word.len() + encoded_size_len(count);
+
+        let mut words = BTreeMap::new();
+
+        let mut size = DICTIONARY_MAGIC.len() + 8;
+
+        let mut candidates = statistics.iter()
+                                       .filter(|&(w,&c)| c > 0 && (w.len() == 1 || c >= min_count))
+                                       .collect::<Vec<(&Vec<u8>,&usize)>>();
+
+        candidates.sort_by(|&(lw,&lc),&(rw,&rc)| {
+            Score::new(lw.len(),lc).cmp(&Score::new(rw.len(),rc)).then(lw.cmp(rw))
+        });
+
+        for (w,&c) in candidates {
+            if size + entry_size(w,c) <= max_size {
+                size += entry_size(w,c);
+
+                words.insert(w.clone(),c);
+            }
+        }
+
+        Dictionary::new(words)
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn words(&self) -> &BTreeMap<Vec<u8>,usize> {
+        &self.words
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn tree(&self) -> &HuffmanTree<Vec<u8>> {
+        &self.tree
+    }
+
+    pub fn table(&self) -> Option<&HuffmanDecodeTable<Vec<u8>>> {
+        self.table.as_ref()
+    }
+
+    pub fn unified_tree(&self) -> &HuffmanTree<Vec<u8>> {
+        &self.unified_tree
+    }
+
+    pub fn unified_table(&self) -> Option<&HuffmanDecodeTable<Vec<u8>>> {
+        self.unified_table.as_ref()
+    }
+
+    pub fn find_words(&self,data:&[u8]) -> BTreeSet<Word> {
+        let mut positions = BTreeMap::new();
+
+        for i in 0..data.len() {
+            positions.entry(data[i..(i + 1)].to_vec()).or_insert(Vec::new()).push((i,i + 1));
+
+            for len in 2..=self.max_word_size.min(data.len() - i) {
+                if let Some((word,_)) = self.words.get_key_value(&data[i..(i + len)]) {
+                    positions.entry(word.clone()).or_insert(Vec::new()).push((i,i + len));
+                }
+            }
+        }
+
+        positions.into_iter().map(|(word,list)| {
+            let mut count = 0;
+            let mut cr = 0;
+
+            for &(l,r) in list.iter() {
+                if cr <= l {
+                    count += 1;
+                    cr = r;
+                }
+            }
+
+            Word::new(word,&list,count)
+        }).collect()
+    }
+
+    fn encode_entries(words:&BTreeMap<Vec<u8>,usize>) -> Result<Vec<u8>,CompressionError> {
+        let mut r = Vec::new();
+
+        {
+            let mut sw = StreamWriter::new(&mut r);
+
+            write_size(&mut sw,words.len())?;
+
+            for (word,&count) in words.iter() {
+                write_size(&mut sw,word.len())?;
+                sw.write_bytes(word)?;
+                write_size(&mut sw,count)?;
+            }
+
+            sw.flush()?;
+        }
+
+        Ok(r)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>,CompressionError> {
+        let mut r = DICTIONARY_MAGIC.to_vec();
+
+        r.extend_from_slice(&Dictionary::encode_entries(&self.words)?);
+        r.extend_from_slice(&self.id.to_le_bytes());
+
+        Ok(r)
+    }
+
+    pub fn from_bytes(bytes:&[u8]) -> Result<Dictionary,UnCompressionError> {
+        if bytes.len() < DICTIONARY_MAGIC.len() + 4 || bytes[..DICTIONARY_MAGIC.len()] != DICTIONARY_MAGIC {
+            return Err(UnCompressionError::FormatError);
+        }
+
+        let entries = &bytes[DICTIONARY_MAGIC.len()..(bytes.len() - 4)];
+
+        let mut id = [0; 4];
+
+        id.copy_from_slice(&bytes[(bytes.len() - 4)..]);
+
+        let expected = u32::from_le_bytes(id);
+        let actual = crc32(entries);
+
+        if expected != actual {
+            return Err(UnCompressionError::ChecksumMismatch { expected, actual });
+        }
+
+        let mut r = entries;
+        let mut sr = StreamReader::new(&mut r);
+
+        let count = read_size(&mut sr)?;
+
+        let mut words = BTreeMap::new();
+
+        for _ in 0..count {
+            let word_size = read_size(&mut sr)?;
+            let word = sr.read_until(word_size)?;
+            let c = read_size(&mut sr)?;
+
+            if word.is_empty() || words.insert(word,c).is_some() {
+                return Err(UnCompressionError::FormatError);
+            }
+        }
+
+        if !sr.is_eof()? {
+            return Err(UnCompressionError::FormatError);
+        }
+
+        Dictionary::new(words).map_err(|e| UnCompressionError::InvalidState(e.to_string()))
+    }
+}
@@ -125,7 +125,15 @@ pub enum UnCompressionError {
     InvalidState(String),
     ReadError(ReadError),
     WriteError(WriteError),
-    FormatError
+    FormatError,
+    NotBlackHoleStream,
+    UnsupportedVersion(u8),
+    ChecksumMismatch {
+        expected: u32,
+        actual: u32
+    },
+    LimitExceeded(String),
+    DictionaryMismatch(u32)
 }
 impl fmt::Display for UnCompressionError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -133,7 +141,14 @@ impl fmt::Display for UnCompressionError {
             UnCompressionError::InvalidState(ref s) => write!(f, "Invalid State. ({})", s),
             UnCompressionError::ReadError(ref e) => write!(f, "Read error ({})", e),
             UnCompressionError::WriteError(ref e) => write!(f, "Write error ({})", e),
-            UnCompressionError::FormatError => write!(f, "The format of the input is invalid.")
+            UnCompressionError::FormatError => write!(f, "The format of the input is invalid."),
+            UnCompressionError::NotBlackHoleStream => write!(f, "The input is not a blackhole stream."),
+            UnCompressionError::UnsupportedVersion(v) => write!(f, "Unsupported format version. (version = {})", v),
+            UnCompressionError::ChecksumMismatch { expected, actual } => {
+                write!(f, "Checksum mismatch. (expected = {:08x}, actual = {:08x})", expected, actual)
+            },
+            UnCompressionError::LimitExceeded(ref s) => write!(f, "Limit exceeded. ({})", s),
+            UnCompressionError::DictionaryMismatch(id) => write!(f, "The required dictionary is not available. (id = {:08x})", id)
         }
     }
 }
@@ -143,7 +158,12 @@ impl error::Error for UnCompressionError {
             UnCompressionError::InvalidState(_) => "Invalid State.",
             UnCompressionError::ReadError(_) => "Read error.",
             UnCompressionError::WriteError(_) => "Write error.",
-            UnCompressionError::FormatError => "The format of the input is invalid."
+            UnCompressionError::FormatError => "The format of the input is invalid.",
+            UnCompressionError::NotBlackHoleStream => "The input is not a blackhole stream.",
+            UnCompressionError::UnsupportedVersion(_) => "Unsupported format version.",
+            UnCompressionError::ChecksumMismatch { .. } => "Checksum mismatch.",
+            UnCompressionError::LimitExceeded(_) => "Limit exceeded.",
+            UnCompressionError::DictionaryMismatch(_) => "The required dictionary is not available."
         }
     }
 
@@ -152,7 +172,12 @@ impl error::Error for UnCompressionError {
             UnCompressionError::InvalidState(_) => None,
             UnCompressionError::ReadError(ref e) => Some(e),
             UnCompressionError::WriteError(ref e) => Some(e),
-            UnCompressionError::FormatError => None
+            UnCompressionError::FormatError => None,
+            UnCompressionError::NotBlackHoleStream => None,
+            UnCompressionError::UnsupportedVersion(_) => None,
+            UnCompressionError::ChecksumMismatch { .. } => None,
+            UnCompressionError::LimitExceeded(_) => None,
+            UnCompressionError::DictionaryMismatch(_) => None
         }
     }
 }
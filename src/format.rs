@@ -0,0 +1,359 @@
+use std::io::{Read, Write};
+use crate::error::{CompressionError, ReadError, UnCompressionError, WriteError};
+use crate::stream::{StreamReader, StreamWriter};
+
+pub const MAGIC:[u8; 4] = [b'B', b'L', b'K', b'H'];
+pub const VERSION_EXPLICIT_CODES:u8 = 1;
+pub const VERSION_CANONICAL_CODES:u8 = 2;
+pub const VERSION_UNIFIED_ALPHABET:u8 = 3;
+
+pub const VERSION:u8 = VERSION_UNIFIED_ALPHABET;
+pub const MIN_VERSION:u8 = VERSION_EXPLICIT_CODES;
+
+pub const FLAG_CONTENT_CHECKSUM:u8 = 0b01;
+pub const FLAG_HEADER_CHECKSUM:u8 = 0b10;
+pub const FLAG_BLOCKS:u8 = 0b100;
+pub const FLAG_SEEK_INDEX:u8 = 0b1000;
+pub const FLAG_DICTIONARY:u8 = 0b10000;
+
+pub const FLAGS_MASK:u8 = FLAG_CONTENT_CHECKSUM | FLAG_HEADER_CHECKSUM | FLAG_BLOCKS | FLAG_SEEK_INDEX | FLAG_DICTIONARY;
+
+pub const HEADER_SIZE:usize = MAGIC.len() + 10;
+
+pub const SIZE_UNKNOWN:u64 = u64::MAX;
+
+pub const BLOCK_END:u8 = 0;
+pub const BLOCK_COMPRESSED:u8 = 1;
+pub const BLOCK_STORED:u8 = 2;
+
+pub const DICTIONARY_MAGIC:[u8; 4] = [b'B', b'H', b'D', b'C'];
+
+pub const INDEX_MAGIC:[u8; 4] = [b'B', b'H', b'I', b'X'];
+pub const INDEX_ENTRY_SIZE:usize = 24;
+pub const FOOTER_SIZE:usize = INDEX_MAGIC.len() + 8;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct Header {
+    version:u8,
+    flags:u8,
+    size:u64
+}
+impl Header {
+    pub fn new(version:u8,flags:u8,size:u64) -> Header {
+        Header {
+            version,
+            flags,
+            size
+        }
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    pub fn has_flag(&self,flag:u8) -> bool {
+        self.flags & flag == flag
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn write<W>(&self,writer:&mut StreamWriter<'_,W>) -> Result<(),WriteError> where W: Write {
+        writer.write_bytes(&MAGIC)?;
+        writer.write(self.version)?;
+        writer.write(self.flags)?;
+        writer.write_u64(self.size)?;
+
+        Ok(())
+    }
+
+    pub fn read<R>(reader:&mut StreamReader<'_,R>) -> Result<Header,UnCompressionError> where R: Read {
+        for &m in MAGIC.iter() {
+            match reader.read_u8() {
+                Ok(b) if b == m => (),
+                Ok(_) | Err(ReadError::UnexpectedEofError) => {
+                    return Err(UnCompressionError::NotBlackHoleStream);
+                },
+                Err(e) => {
+                    return Err(UnCompressionError::from(e));
+                }
+            }
+        }
+
+        let version = reader.read_u8()?;
+
+        if !(MIN_VERSION..=VERSION).contains(&version) {
+            return Err(UnCompressionError::UnsupportedVersion(version));
+        }
+
+        let flags = reader.read_u8()?;
+
+        if flags & !FLAGS_MASK != 0 {
+            return Err(UnCompressionError::FormatError);
+        }
+
+        let size = reader.read_u64()?;
+
+        if flags & FLAG_BLOCKS == 0 && (size == SIZE_UNKNOWN || flags & FLAG_SEEK_INDEX != 0) {
+            return Err(UnCompressionError::FormatError);
+        }
+
+        Ok(Header {
+            version,
+            flags,
+            size
+        })
+    }
+}
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct BlockHeader {
+    block_type:u8,
+    size:usize,
+    compressed_size:usize
+}
+impl BlockHeader {
+    pub fn new(block_type:u8,size:usize,compressed_size:usize) -> BlockHeader {
+        BlockHeader {
+            block_type,
+            size,
+            compressed_size
+        }
+    }
+
+    pub fn end() -> BlockHeader {
+        BlockHeader::new(BLOCK_END,0,0)
+    }
+
+    pub fn block_type(&self) -> u8 {
+        self.block_type
+    }
+
+    pub fn is_end(&self) -> bool {
+        self.block_type == BLOCK_END
+    }
+
+    pub fn is_stored(&self) -> bool {
+        self.block_type == BLOCK_STORED
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn compressed_size(&self) -> usize {
+        self.compressed_size
+    }
+
+    pub fn encoded_size(&self) -> usize {
+        if self.block_type == BLOCK_END {
+            1
+        } else {
+            1 + encoded_size_len(self.size) + encoded_size_len(self.compressed_size)
+        }
+    }
+
+    pub fn write<W>(&self,writer:&mut StreamWriter<'_,W>) -> Result<(),CompressionError> where W: Write {
+        writer.write(self.block_type)?;
+
+        if self.block_type != BLOCK_END {
+            write_size(writer,self.size)?;
+            write_size(writer,self.compressed_size)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read<R>(reader:&mut StreamReader<'_,R>) -> Result<BlockHeader,UnCompressionError> where R: Read {
+        let block_type = reader.read_u8()?;
+
+        if block_type == BLOCK_END {
+            Ok(BlockHeader::end())
+        } else if block_type == BLOCK_COMPRESSED || block_type == BLOCK_STORED {
+            let size = read_size(reader)?;
+            let compressed_size = read_size(reader)?;
+
+            Ok(BlockHeader::new(block_type,size,compressed_size))
+        } else {
+            Err(UnCompressionError::FormatError)
+        }
+    }
+}
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct BlockIndexEntry {
+    compressed_offset:u64,
+    uncompressed_offset:u64,
+    size:u64
+}
+impl BlockIndexEntry {
+    pub fn new(compressed_offset:u64,uncompressed_offset:u64,size:u64) -> BlockIndexEntry {
+        BlockIndexEntry {
+            compressed_offset,
+            uncompressed_offset,
+            size
+        }
+    }
+
+    pub fn compressed_offset(&self) -> u64 {
+        self.compressed_offset
+    }
+
+    pub fn uncompressed_offset(&self) -> u64 {
+        self.uncompressed_offset
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct BlockIndex {
+    entries:Vec<BlockIndexEntry>
+}
+impl BlockIndex {
+    pub fn new() -> BlockIndex {
+        BlockIndex {
+            entries: Vec::new()
+        }
+    }
+
+    pub fn push(&mut self,compressed_offset:u64,size:u64) {
+        let uncompressed_offset = self.total_size();
+
+        self.entries.push(BlockIndexEntry::new(compressed_offset,uncompressed_offset,size));
+    }
+
+    pub fn entries(&self) -> &[BlockIndexEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn total_size(&self) -> u64 {
+        self.entries.last().map(|e| e.uncompressed_offset + e.size).unwrap_or(0)
+    }
+
+    pub fn find(&self,offset:u64) -> Option<usize> {
+        if offset >= self.total_size() {
+            return None;
+        }
+
+        Some(self.entries.partition_point(|e| e.uncompressed_offset + e.size <= offset))
+    }
+
+    pub fn write<W>(&self,writer:&mut StreamWriter<'_,W>,index_offset:u64) -> Result<(),WriteError> where W: Write {
+        writer.write_u64(self.entries.len() as u64)?;
+
+        for e in self.entries.iter() {
+            writer.write_u64(e.compressed_offset)?;
+            writer.write_u64(e.uncompressed_offset)?;
+            writer.write_u64(e.size)?;
+        }
+
+        writer.write_u64(index_offset)?;
+        writer.write_bytes(&INDEX_MAGIC)?;
+
+        Ok(())
+    }
+
+    pub fn read<R>(reader:&mut StreamReader<'_,R>) -> Result<(BlockIndex,u64),UnCompressionError> where R: Read {
+        let count = reader.read_u64()?;
+
+        let mut entries = Vec::with_capacity(count.min(1024) as usize);
+
+        for _ in 0..count {
+            let compressed_offset = reader.read_u64()?;
+            let uncompressed_offset = reader.read_u64()?;
+            let size = reader.read_u64()?;
+
+            let expected = entries.last().map(|e:&BlockIndexEntry| e.uncompressed_offset + e.size).unwrap_or(0);
+
+            if uncompressed_offset != expected || uncompressed_offset.checked_add(size).is_none() {
+                return Err(UnCompressionError::FormatError);
+            }
+
+            entries.push(BlockIndexEntry::new(compressed_offset,uncompressed_offset,size));
+        }
+
+        let index_offset = read_footer(reader)?;
+
+        Ok((BlockIndex {
+            entries
+        },index_offset))
+    }
+}
+impl Default for BlockIndex {
+    fn default() -> Self {
+        BlockIndex::new()
+    }
+}
+pub fn read_footer<R>(reader:&mut StreamReader<'_,R>) -> Result<u64,UnCompressionError> where R: Read {
+    let index_offset = reader.read_u64()?;
+
+    for &m in INDEX_MAGIC.iter() {
+        if reader.read_u8()? != m {
+            return Err(UnCompressionError::FormatError);
+        }
+    }
+
+    Ok(index_offset)
+}
+pub fn write_size<W>(writer:&mut StreamWriter<'_,W>,size:usize) -> Result<(),CompressionError> where W: Write {
+    if size < 1 << 6 {
+        writer.write((size as u8) << 2)?;
+    } else if size < 1 << 14 {
+        writer.write_u16(((size as u16) << 2) | 0b01)?;
+    } else if size < 1 << 30 {
+        writer.write_u32(((size as u32) << 2) | 0b10)?;
+    } else if size < 1 << 62 {
+        writer.write_u64(((size as u64) << 2) | 0b11)?;
+    } else {
+        return Err(CompressionError::LimitError(String::from("Data size is too large.")))
+    }
+
+    Ok(())
+}
+pub fn encoded_size_len(size:usize) -> usize {
+    if size < 1 << 6 {
+        1
+    } else if size < 1 << 14 {
+        2
+    } else if size < 1 << 30 {
+        4
+    } else {
+        8
+    }
+}
+pub fn size_len(first:u8) -> usize {
+    1 << (first & 0b11)
+}
+pub fn read_size<R>(reader:&mut StreamReader<'_,R>) -> Result<usize,UnCompressionError> where R: Read {
+    let h = reader.get_bits_from_lsb(2)?;
+
+    let size = if h == 0b00 {
+        reader.get_bits_from_lsb(6)? as usize
+    } else if h == 0b01 {
+        (reader.get_bits_from_lsb(6)? as usize) | ((reader.read_u8()? as usize) << 6)
+    } else if h == 0b10 {
+        (reader.get_bits_from_lsb(6)? as usize) | ((reader.read_u8()? as usize) << 6) | ((reader.read_u16()? as usize) << 14)
+    } else if h == 0b11 {
+        (reader.get_bits_from_lsb(6)? as usize) |
+        ((reader.read_u8()? as usize) << 6) |
+        ((reader.read_u16()? as usize) << 14) |
+        ((reader.read_u32()? as usize) << 30)
+    } else {
+        return Err(UnCompressionError::FormatError);
+    };
+
+    Ok(size)
+}
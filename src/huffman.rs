@@ -7,6 +7,11 @@ use crate::error::{CompressionError, ReadError, UnCompressionError, WriteError};
 use crate::Score;
 use crate::stream::{StreamReader, StreamWriter};
 
+pub const MAX_CANONICAL_CODE_LENGTH:usize = 127;
+pub const PRIMARY_TABLE_BITS:usize = 10;
+pub const MAX_TABLE_CODE_LENGTH:usize = 24;
+const MAX_TABLE_ENTRIES:usize = 1 << 20;
+
 #[derive(Debug,Clone)]
 pub enum HuffmanNode<T> where T: Ord + Clone + Default {
     Node {
@@ -17,6 +22,7 @@ pub enum HuffmanNode<T> where T: Ord + Clone + Default {
         word: T
     }
 }
+#[allow(clippy::redundant_field_names,clippy::match_ref_pats,clippy::needless_borrowed_reference)]
 impl<T> HuffmanNode<T> where T: Ord + Clone + Default {
     pub fn new(word:T) -> HuffmanNode<T> {
         HuffmanNode::Leaf {
@@ -28,58 +34,57 @@ impl<T> HuffmanNode<T> where T: Ord + Clone + Default {
         HuffmanNode::Leaf { word: T::default() }
     }
 
-    fn insert(self:Box<Self>,word:T,bits:Bits,index:usize) -> Result<Box<Self>,UnCompressionError> {
-        match *self {
-            HuffmanNode::Leaf { word: _} => {
-                if index >= bits.len {
-                    Ok(Box::new(HuffmanNode::Leaf { word: word }))
-                } else {
-                    let b = bits.get_bit(index)?;
+    fn insert(&mut self,word:T,bits:&Bits) -> Result<(),UnCompressionError> {
+        let mut node = self;
+
+        for index in 0..bits.len {
+            if let HuffmanNode::Leaf { .. } = node {
+                *node = HuffmanNode::Node {
+                    left: Box::new(HuffmanNode::empty()),
+                    right: Box::new(HuffmanNode::empty())
+                };
+            }
 
-                    Ok(if b == 0u8 {
-                        Box::new(HuffmanNode::Node {
-                            left: self.insert(word,bits,index+1)?,
-                            right: Box::new(HuffmanNode::empty())
-                        })
+            node = match node {
+                HuffmanNode::Node { left, right } => {
+                    if bits.get_bit(index)? == 0 {
+                        left.as_mut()
                     } else {
-                        Box::new(HuffmanNode::Node {
-                            left: Box::new(HuffmanNode::empty()),
-                            right: self.insert(word,bits,index+1)?
-                        })
-                    })
+                        right.as_mut()
+                    }
+                },
+                HuffmanNode::Leaf { .. } => {
+                    return Err(UnCompressionError::from(ReadError::InvalidState(String::from("Huffman node status is invalid."))));
                 }
+            };
+        }
+
+        match node {
+            HuffmanNode::Leaf { .. } => {
+                *node = HuffmanNode::Leaf { word };
+
+                Ok(())
             },
-            HuffmanNode::Node {
-                left: l,
-                right: r
-            } => {
-                if index < bits.len && bits.get_bit(index)? == 0 {
-                    Ok(Box::new(HuffmanNode::Node {
-                        left: l.insert(word,bits,index+1)?,
-                        right: r
-                    }))
-                } else if index < bits.len && bits.get_bit(index)? == 1 {
-                    Ok(Box::new(HuffmanNode::Node {
-                        left: l,
-                        right: r.insert(word,bits,index+1)?
-                    }))
-                } else {
-                    Err(UnCompressionError::from(ReadError::InvalidState(String::from("Huffman node status is invalid."))))
-                }
+            HuffmanNode::Node { .. } => {
+                Err(UnCompressionError::from(ReadError::InvalidState(String::from("Huffman node status is invalid."))))
             }
         }
     }
 
     fn find_word<R>(&self,reader:&mut StreamReader<'_,R>) -> Result<&T,ReadError> where R: Read {
-        match self {
-            &HuffmanNode::Leaf { ref word} => {
-                Ok(word)
-            },
-            &HuffmanNode::Node { ref left, ref right } => {
-                if reader.get_bit_from_lsb()? == 0 {
-                    left.find_word(reader)
-                } else {
-                    right.find_word(reader)
+        let mut node = self;
+
+        loop {
+            match node {
+                HuffmanNode::Leaf { word } => {
+                    return Ok(word);
+                },
+                HuffmanNode::Node { left, right } => {
+                    node = if reader.get_bit_from_lsb()? == 0 {
+                        left
+                    } else {
+                        right
+                    };
                 }
             }
         }
@@ -108,22 +113,26 @@ impl<T> HuffmanNode<T> where T: Ord + Clone + Default {
 #[derive(Debug,Clone)]
 pub struct HuffmanItem<T> where T: Ord + Clone + Default {
     node:HuffmanNode<T>,
-    score:usize
+    score:usize,
+    id:usize
 }
+#[allow(clippy::redundant_field_names)]
 impl<T> HuffmanItem<T> where T: Ord + Clone + Default {
-    pub fn new(node:HuffmanNode<T>,score:usize) -> HuffmanItem<T> {
+    pub fn new(node:HuffmanNode<T>,score:usize,id:usize) -> HuffmanItem<T> {
         HuffmanItem {
             node:node,
-            score:score
+            score:score,
+            id:id
         }
     }
 }
 impl<T> Ord for HuffmanItem<T> where T: Ord + Clone + Default {
     fn cmp(&self, other: &Self) -> Ordering {
         self.score.cmp(&other.score).reverse()
-            .then((&self.node as *const HuffmanNode<T> as usize).cmp(&(&other.node as *const HuffmanNode<T> as usize)))
+            .then(self.id.cmp(&other.id).reverse())
     }
 }
+#[allow(clippy::needless_borrow)]
 impl<T> PartialOrd for HuffmanItem<T> where T: Ord + Clone + Default {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(&other))
@@ -131,15 +140,21 @@ impl<T> PartialOrd for HuffmanItem<T> where T: Ord + Clone + Default {
 }
 impl<T> PartialEq for HuffmanItem<T> where T: Ord + Clone + Default {
     fn eq(&self, other: &Self) -> bool {
-        &self.node as *const HuffmanNode<T> as usize == &other.node as *const HuffmanNode<T> as usize
+        self.score == other.score && self.id == other.id
     }
 }
 impl<T> Eq for HuffmanItem<T> where T: Ord + Clone + Default {}
-#[derive(Debug,Clone)]
+#[derive(Debug,Clone,Copy)]
+enum PackageItem {
+    Leaf(usize),
+    Package(usize,usize)
+}
+#[derive(Debug,Clone,PartialEq,Eq)]
 pub struct Bits {
     len:usize,
     data:Vec<u8>
 }
+#[allow(clippy::new_without_default,clippy::len_without_is_empty,clippy::needless_bool)]
 impl Bits {
     pub fn new() -> Bits {
         Bits {
@@ -176,7 +191,19 @@ impl Bits {
         }
     }
 
+    pub fn to_u64(&self) -> Option<u64> {
+        if self.len > 64 {
+            None
+        } else {
+            Some(self.data.iter().enumerate().fold(0u64, |acc,(i,&b)| acc | (b as u64) << (i * 8)))
+        }
+    }
+
     pub fn write<'a,W>(&self,writer:&mut StreamWriter<'a,W>) -> Result<(),WriteError> where W: Write {
+        if let Some(value) = self.to_u64() {
+            return writer.write_bits(value,self.len);
+        }
+
         let len = self.len;
 
         for i in 0..len {
@@ -196,23 +223,29 @@ pub struct HuffmanTree<T> where T: Ord + Clone + Default + Debug {
     dic:BTreeMap<T,Bits>
 }
 impl<T> HuffmanTree<T> where T: Ord + Clone + Default + Debug {
+    #[allow(clippy::option_map_unit_fn)]
     pub fn new(words:Vec<(T,Score)>) -> HuffmanTree<T> {
         let mut queue = BinaryHeap::new();
 
+        let mut id = 0;
+
         for (w,s) in words {
-            queue.push(HuffmanItem::new(HuffmanNode::Leaf { word: w },s.count));
+            queue.push(HuffmanItem::new(HuffmanNode::Leaf { word: w },s.count,id));
+            id += 1;
         }
 
         while queue.len() > 1 {
             let l = queue.pop().unwrap();
             let r = queue.pop().unwrap();
 
-            let score = l.score + r.score;
+            let score = l.score.saturating_add(r.score);
 
             queue.push(HuffmanItem::new(HuffmanNode::Node {
                 left: Box::new(l.node),
                 right: Box::new(r.node)
-            }, score))
+            }, score, id));
+
+            id += 1;
         }
 
         let mut r = HuffmanTree {
@@ -222,15 +255,181 @@ impl<T> HuffmanTree<T> where T: Ord + Clone + Default + Debug {
 
         let mut dic = BTreeMap::new();
 
-        r.root.as_ref().map(|root| {
-            Self::build_dic(&mut dic, root, Bits::new());
-        });
+        if let Some(HuffmanNode::Leaf { word }) = r.root.as_deref() {
+            let mut bits = Bits::new();
+
+            bits.push_bit(false);
+            dic.insert(word.clone(),bits);
+
+            r.root = r.root.take().map(|leaf| Box::new(HuffmanNode::Node {
+                left: leaf,
+                right: Box::new(HuffmanNode::empty())
+            }));
+        } else {
+            r.root.as_ref().map(|root| {
+                Self::build_dic(&mut dic, root, Bits::new());
+            });
+        }
 
         r.dic = dic;
 
         r
     }
 
+    pub fn canonical(words:Vec<(T,Score)>) -> Result<HuffmanTree<T>,CompressionError> {
+        Self::length_limited(words,MAX_CANONICAL_CODE_LENGTH)
+    }
+
+    pub fn length_limited(words:Vec<(T,Score)>,max_len:usize) -> Result<HuffmanTree<T>,CompressionError> {
+        if max_len < usize::BITS as usize && words.len() > 1 << max_len {
+            return Err(CompressionError::LimitError(
+                String::from("The number of words exceeds the capacity of the maximum Huffman code length.")
+            ));
+        }
+
+        let tree = HuffmanTree::new(words.clone());
+
+        let lengths = if tree.dic.values().any(|bits| bits.len() > max_len) {
+            Self::package_merge(words,max_len)
+        } else {
+            tree.code_lengths().into_iter().map(|(w,l)| (w.clone(),l)).collect::<Vec<(T,usize)>>()
+        };
+
+        Self::canonical_codes(lengths).ok_or(
+            CompressionError::LimitError(String::from("The size of the Huffman sign is too large."))
+        ).and_then(|codes| {
+            Self::from_codes(codes).map_err(|e| CompressionError::InvalidState(e.to_string()))
+        })
+    }
+
+    fn package_merge(mut words:Vec<(T,Score)>,max_len:usize) -> Vec<(T,usize)> {
+        words.sort_by(|(wa,sa),(wb,sb)| sa.count.cmp(&sb.count).then(wa.cmp(wb)));
+
+        let n = words.len();
+
+        let mut items = words.iter().enumerate().map(|(i,(_,s))| {
+            (s.count as u128,PackageItem::Leaf(i))
+        }).collect::<Vec<(u128,PackageItem)>>();
+
+        let leaves = (0..n).collect::<Vec<usize>>();
+        let mut current = leaves.clone();
+
+        for _ in 1..max_len {
+            let mut merged = Vec::with_capacity(n * 2);
+            let mut packages = Vec::with_capacity(current.len() / 2);
+
+            for pair in current.chunks_exact(2) {
+                let weight = items[pair[0]].0 + items[pair[1]].0;
+
+                items.push((weight,PackageItem::Package(pair[0],pair[1])));
+                packages.push(items.len() - 1);
+            }
+
+            let mut l = leaves.iter().peekable();
+            let mut p = packages.iter().peekable();
+
+            loop {
+                match (l.peek(),p.peek()) {
+                    (Some(&&a),Some(&&b)) => {
+                        if items[a].0 <= items[b].0 {
+                            merged.push(a);
+                            l.next();
+                        } else {
+                            merged.push(b);
+                            p.next();
+                        }
+                    },
+                    (Some(&&a),None) => {
+                        merged.push(a);
+                        l.next();
+                    },
+                    (None,Some(&&b)) => {
+                        merged.push(b);
+                        p.next();
+                    },
+                    (None,None) => break
+                }
+            }
+
+            current = merged;
+        }
+
+        let mut lengths = vec![0; n];
+        let mut stack = current.into_iter().take((2 * n).saturating_sub(2)).collect::<Vec<usize>>();
+
+        while let Some(i) = stack.pop() {
+            match items[i].1 {
+                PackageItem::Leaf(w) => {
+                    lengths[w] += 1;
+                },
+                PackageItem::Package(a,b) => {
+                    stack.push(a);
+                    stack.push(b);
+                }
+            }
+        }
+
+        words.into_iter().zip(lengths).map(|((w,_),l)| (w,l)).collect()
+    }
+
+    pub fn from_lengths(lengths:Vec<(T,usize)>) -> Result<HuffmanTree<T>,UnCompressionError> {
+        let codes = Self::canonical_codes(lengths).ok_or(UnCompressionError::FormatError)?;
+
+        Self::from_codes(codes)
+    }
+
+    fn from_codes(codes:Vec<(T,Bits)>) -> Result<HuffmanTree<T>,UnCompressionError> {
+        let mut tree = HuffmanTree::empty();
+
+        for (w,bits) in codes {
+            tree.insert(w,bits)?;
+        }
+
+        Ok(tree)
+    }
+
+    fn canonical_codes(mut lengths:Vec<(T,usize)>) -> Option<Vec<(T,Bits)>> {
+        lengths.sort_by(|(wa,la),(wb,lb)| la.cmp(lb).then(wa.cmp(wb)));
+
+        let mut codes = Vec::with_capacity(lengths.len());
+
+        let mut code = 0u128;
+        let mut prev_len = 0;
+
+        for (w,len) in lengths {
+            if len > MAX_CANONICAL_CODE_LENGTH {
+                return None;
+            }
+
+            code <<= len - prev_len;
+
+            if code >= 1u128 << len {
+                return None;
+            }
+
+            let mut bits = Bits::new();
+
+            for i in (0..len).rev() {
+                bits.push_bit(code & (1u128 << i) != 0);
+            }
+
+            codes.push((w,bits));
+
+            code += 1;
+            prev_len = len;
+        }
+
+        Some(codes)
+    }
+
+    pub fn code_lengths(&self) -> Vec<(&T,usize)> {
+        let mut lengths = self.dic.iter().map(|(w,bits)| (w,bits.len())).collect::<Vec<(&T,usize)>>();
+
+        lengths.sort_by(|(wa,la),(wb,lb)| la.cmp(lb).then(wa.cmp(wb)));
+
+        lengths
+    }
+
     pub fn empty() -> HuffmanTree<T> {
         HuffmanTree {
             root: None,
@@ -238,6 +437,7 @@ impl<T> HuffmanTree<T> where T: Ord + Clone + Default + Debug {
         }
     }
 
+    #[allow(clippy::borrowed_box,clippy::match_ref_pats,clippy::needless_borrow)]
     fn build_dic(dic:&mut BTreeMap<T,Bits>, node: &Box<HuffmanNode<T>>, bits:Bits) {
         match &node.deref() {
             &HuffmanNode::Leaf { word } => {
@@ -260,15 +460,13 @@ impl<T> HuffmanTree<T> where T: Ord + Clone + Default + Debug {
     }
 
     pub fn insert(&mut self,word:T,bits:Bits) -> Result<(),UnCompressionError> {
-        if let Some(root) = self.root.take() {
-            self.root = Some(root.insert(word,bits,0)?);
-        } else {
-            let mut root = Box::new(HuffmanNode::empty());
+        if bits.len() == 0 {
+            return Err(UnCompressionError::FormatError);
+        }
 
-            root = root.insert(word,bits,0)?;
+        self.root.get_or_insert_with(|| Box::new(HuffmanNode::empty())).insert(word.clone(),&bits)?;
+        self.dic.insert(word,bits);
 
-            self.root = Some(root);
-        }
         Ok(())
     }
 
@@ -288,7 +486,7 @@ impl<T> HuffmanTree<T> where T: Ord + Clone + Default + Debug {
 
     pub fn words(&self) -> Vec<&T> {
         if let Some(root) = &self.root {
-            root.words()
+            root.words().into_iter().filter(|w| self.dic.contains_key(w)).collect()
         } else {
             Vec::new()
         }
@@ -298,6 +496,7 @@ impl<T> HuffmanTree<T> where T: Ord + Clone + Default + Debug {
         self.dic.get(word)
     }
 
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> usize {
         self.dic.len()
     }
@@ -305,4 +504,106 @@ impl<T> HuffmanTree<T> where T: Ord + Clone + Default + Debug {
     pub fn contains_word(&self,word:&T) -> bool {
         self.dic.contains_key(word)
     }
-}
\ No newline at end of file
+}
+#[derive(Debug,Clone,Copy)]
+enum TableEntry {
+    Invalid,
+    Word {
+        index:usize,
+        len:usize
+    },
+    Secondary {
+        offset:usize,
+        bits:usize
+    }
+}
+#[derive(Debug)]
+pub struct HuffmanDecodeTable<T> where T: Ord + Clone + Default + Debug {
+    words:Vec<T>,
+    primary_bits:usize,
+    primary:Vec<TableEntry>,
+    secondary:Vec<TableEntry>
+}
+impl<T> HuffmanDecodeTable<T> where T: Ord + Clone + Default + Debug {
+    pub fn new(tree:&HuffmanTree<T>) -> Option<HuffmanDecodeTable<T>> {
+        let max_len = tree.dic.values().map(|bits| bits.len()).max()?;
+
+        if max_len > MAX_TABLE_CODE_LENGTH {
+            return None;
+        }
+
+        let primary_bits = max_len.min(PRIMARY_TABLE_BITS);
+        let primary_mask = (1u64 << primary_bits) - 1;
+
+        let mut primary = vec![TableEntry::Invalid; 1 << primary_bits];
+        let mut secondary = Vec::new();
+
+        let mut groups = BTreeMap::new();
+
+        for (index,bits) in tree.dic.values().enumerate() {
+            let code = bits.to_u64()?;
+            let len = bits.len();
+
+            if len <= primary_bits {
+                let mut i = code as usize;
+
+                while i < primary.len() {
+                    primary[i] = TableEntry::Word { index, len };
+                    i += 1 << len;
+                }
+            } else {
+                groups.entry(code & primary_mask).or_insert(Vec::new()).push((index,code >> primary_bits,len - primary_bits));
+            }
+        }
+
+        for (prefix,codes) in groups {
+            let bits = codes.iter().map(|&(_,_,len)| len).max().unwrap_or(0);
+            let offset = secondary.len();
+
+            if offset + (1 << bits) > MAX_TABLE_ENTRIES {
+                return None;
+            }
+
+            secondary.resize(offset + (1 << bits),TableEntry::Invalid);
+
+            for (index,code,len) in codes {
+                let mut i = code as usize;
+
+                while i < 1 << bits {
+                    secondary[offset + i] = TableEntry::Word { index, len: len + primary_bits };
+                    i += 1 << len;
+                }
+            }
+
+            primary[prefix as usize] = TableEntry::Secondary { offset, bits };
+        }
+
+        Some(HuffmanDecodeTable {
+            words: tree.dic.keys().cloned().collect(),
+            primary_bits,
+            primary,
+            secondary
+        })
+    }
+
+    pub fn find_word<R>(&self,reader:&mut StreamReader<'_,R>) -> Result<&T,ReadError> where R: Read {
+        let entry = self.primary[reader.peek_bits(self.primary_bits)? as usize];
+
+        let entry = if let TableEntry::Secondary { offset, bits } = entry {
+            self.secondary[offset + (reader.peek_bits(self.primary_bits + bits)? >> self.primary_bits) as usize]
+        } else {
+            entry
+        };
+
+        match entry {
+            TableEntry::Word { index, len } => {
+                reader.consume(len)?;
+
+                Ok(&self.words[index])
+            },
+            _ => {
+                Err(ReadError::InvalidState(String::from("The Huffman code is invalid.")))
+            }
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use std::io;
+use std::io::Read;
+
+use crate::BlackHole;
+use crate::error::UnCompressionError;
+use crate::format::{BlockHeader, BlockIndex, FLAG_BLOCKS, FLAG_SEEK_INDEX, Header, HEADER_SIZE};
+use crate::huffman::Bits;
+use crate::stream::{StreamReader, StreamWriter};
+
+#[derive(Debug,Clone)]
+pub struct BlockInfo {
+    size:usize,
+    compressed_size:usize,
+    framing_size:usize,
+    dictionary_size:usize,
+    shared_dictionary:Option<u32>,
+    literal_tokens:usize,
+    word_tokens:usize,
+    word_bytes:usize,
+    words:Vec<(Vec<u8>,Bits)>,
+    collect_words:bool,
+    stored:bool
+}
+impl BlockInfo {
+    pub fn new() -> BlockInfo {
+        BlockInfo {
+            size: 0,
+            compressed_size: 0,
+            framing_size: 0,
+            dictionary_size: 0,
+            shared_dictionary: None,
+            literal_tokens: 0,
+            word_tokens: 0,
+            word_bytes: 0,
+            words: Vec::new(),
+            collect_words: false,
+            stored: false
+        }
+    }
+
+    pub(crate) fn with_words() -> BlockInfo {
+        BlockInfo {
+            collect_words: true,
+            ..BlockInfo::new()
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn compressed_size(&self) -> usize {
+        self.compressed_size
+    }
+
+    pub fn framing_size(&self) -> usize {
+        self.framing_size
+    }
+
+    pub fn dictionary_size(&self) -> usize {
+        self.dictionary_size
+    }
+
+    pub fn payload_size(&self) -> usize {
+        self.compressed_size - self.dictionary_size
+    }
+
+    pub fn shared_dictionary(&self) -> Option<u32> {
+        self.shared_dictionary
+    }
+
+    pub fn literal_tokens(&self) -> usize {
+        self.literal_tokens
+    }
+
+    pub fn word_tokens(&self) -> usize {
+        self.word_tokens
+    }
+
+    pub fn word_bytes(&self) -> usize {
+        self.word_bytes
+    }
+
+    pub fn words(&self) -> &[(Vec<u8>,Bits)] {
+        &self.words
+    }
+
+    pub fn is_stored(&self) -> bool {
+        self.stored
+    }
+
+    pub(crate) fn collect_words(&self) -> bool {
+        self.collect_words
+    }
+
+    pub(crate) fn set_dictionary(&mut self,dictionary_size:usize,shared_dictionary:Option<u32>,words:Vec<(Vec<u8>,Bits)>) {
+        self.dictionary_size = dictionary_size;
+        self.shared_dictionary = shared_dictionary;
+        self.words = words;
+    }
+
+    pub(crate) fn set_stored(&mut self) {
+        self.stored = true;
+    }
+
+    pub(crate) fn add_literal(&mut self) {
+        self.literal_tokens += 1;
+    }
+
+    pub(crate) fn add_word(&mut self,len:usize) {
+        self.word_tokens += 1;
+        self.word_bytes += len;
+    }
+}
+impl Default for BlockInfo {
+    fn default() -> Self {
+        BlockInfo::new()
+    }
+}
+#[derive(Debug,Clone)]
+pub struct StreamInfo {
+    header:Header,
+    blocks:Vec<BlockInfo>,
+    end_size:usize,
+    index_size:usize
+}
+impl StreamInfo {
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn blocks(&self) -> &[BlockInfo] {
+        &self.blocks
+    }
+
+    pub fn header_size(&self) -> usize {
+        HEADER_SIZE
+    }
+
+    pub fn index_size(&self) -> usize {
+        self.index_size
+    }
+
+    pub fn size(&self) -> u64 {
+        self.blocks.iter().map(|b| b.size as u64).sum()
+    }
+
+    pub fn framing_size(&self) -> usize {
+        self.blocks.iter().map(|b| b.framing_size).sum::<usize>() + self.end_size
+    }
+
+    pub fn dictionary_size(&self) -> usize {
+        self.blocks.iter().map(|b| b.dictionary_size).sum()
+    }
+
+    pub fn payload_size(&self) -> usize {
+        self.blocks.iter().map(|b| b.payload_size()).sum()
+    }
+
+    pub fn compressed_size(&self) -> u64 {
+        (HEADER_SIZE + self.framing_size() + self.index_size) as u64 + self.blocks.iter().map(|b| b.compressed_size as u64).sum::<u64>()
+    }
+
+    pub fn literal_tokens(&self) -> usize {
+        self.blocks.iter().map(|b| b.literal_tokens).sum()
+    }
+
+    pub fn word_tokens(&self) -> usize {
+        self.blocks.iter().map(|b| b.word_tokens).sum()
+    }
+
+    pub fn word_bytes(&self) -> usize {
+        self.blocks.iter().map(|b| b.word_bytes).sum()
+    }
+}
+impl BlackHole {
+    pub fn inspect<R>(&mut self,reader:&mut StreamReader<'_,R>) -> Result<StreamInfo,UnCompressionError> where R: Read {
+        let header = Header::read(reader)?;
+
+        let mut sink = io::sink();
+        let mut blocks = Vec::new();
+        let mut end_size = 0;
+        let mut index_size = 0;
+        let mut total_size = 0u64;
+
+        if header.has_flag(FLAG_BLOCKS) {
+            loop {
+                let block_header = BlockHeader::read(reader)?;
+
+                if block_header.is_end() {
+                    end_size = block_header.encoded_size();
+                    break;
+                }
+
+                total_size = total_size.checked_add(block_header.size() as u64).ok_or(UnCompressionError::FormatError)?;
+
+                self.check_output_size(total_size)?;
+
+                let body = reader.read_until(block_header.compressed_size())?;
+
+                let mut br = &body[..];
+                let mut br = StreamReader::new(&mut br);
+                let mut sw = StreamWriter::new(&mut sink);
+
+                let mut info = BlockInfo::with_words();
+
+                self.uncompress_block_with_info(&mut br,&mut sw,&header,&block_header,&mut info)?;
+
+                info.size = block_header.size();
+                info.compressed_size = block_header.compressed_size();
+                info.framing_size = block_header.encoded_size();
+
+                blocks.push(info);
+            }
+
+            if header.has_flag(FLAG_SEEK_INDEX) {
+                let start = reader.bit_position();
+
+                BlockIndex::read(reader)?;
+
+                index_size = ((reader.bit_position() - start) / 8) as usize;
+            }
+        } else {
+            self.check_output_size(header.size())?;
+
+            let start = reader.bit_position();
+
+            let mut sw = StreamWriter::new(&mut sink);
+
+            let mut info = BlockInfo::with_words();
+
+            self.uncompress_data_with_info(reader,&mut sw,&header,header.size() as usize,&mut info)?;
+
+            reader.align_to_byte();
+
+            info.size = header.size() as usize;
+            info.compressed_size = ((reader.bit_position() - start) / 8) as usize;
+
+            blocks.push(info);
+        }
+
+        Ok(StreamInfo {
+            header,
+            blocks,
+            end_size,
+            index_size
+        })
+    }
+}
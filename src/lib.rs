@@ -5,36 +5,95 @@ use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Debug;
 use std::io::Write;
 use std::io::Read;
-use std::sync::Arc;
+use std::ops::Deref;
 
 use rayon::iter::IntoParallelIterator;
 use rayon::iter::ParallelIterator;
 use rayon::iter::IntoParallelRefIterator;
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
 
 use crate::error::{ReadError, CompressionError, UnCompressionError};
-use crate::huffman::{Bits, HuffmanTree};
+use crate::checksum::{crc32, Crc32};
+use crate::format::{BLOCK_COMPRESSED, BLOCK_STORED, BlockHeader, BlockIndex, FLAG_BLOCKS, FLAG_DICTIONARY, FLAG_SEEK_INDEX, HEADER_SIZE, FLAG_CONTENT_CHECKSUM, FLAG_HEADER_CHECKSUM, Header, MIN_VERSION, read_size, SIZE_UNKNOWN, VERSION, VERSION_CANONICAL_CODES, VERSION_UNIFIED_ALPHABET, write_size};
+use crate::huffman::{Bits, HuffmanDecodeTable, HuffmanTree, MAX_CANONICAL_CODE_LENGTH};
+use crate::inspect::BlockInfo;
+use crate::options::{AnalysisMethod, BlackHoleOptions, DecodeLimits, DEFAULT_BLOCK_SIZE};
+use crate::score::{ScoreContext, ScoreModel};
+use crate::suffix_array::{lcp_array, suffix_array};
 use crate::stream::{StreamReader, StreamWriter};
 
 pub mod error;
 pub mod stream;
 pub mod huffman;
 pub mod num;
+pub mod format;
+pub mod checksum;
+pub mod options;
+pub mod codec;
+pub mod dictionary;
+pub mod inspect;
+pub mod suffix_array;
+pub mod score;
 
+pub fn compress(data:&[u8]) -> Result<Vec<u8>,CompressionError> {
+    let mut reader = data;
+    let mut sr = StreamReader::new(&mut reader);
+
+    let mut r = Vec::new();
+
+    {
+        let mut sw = StreamWriter::new(&mut r);
+
+        BlackHole::new().compression(&mut sr,&mut sw)?;
+    }
+
+    Ok(r)
+}
+pub fn decompress(data:&[u8]) -> Result<Vec<u8>,UnCompressionError> {
+    decompress_with_limits(data,DecodeLimits::new())
+}
+pub fn decompress_with_limit(data:&[u8],limit:usize) -> Result<Vec<u8>,UnCompressionError> {
+    decompress_with_limits(data,DecodeLimits::builder().max_output_size(limit as u64).build())
+}
+fn decompress_with_limits(data:&[u8],limits:DecodeLimits) -> Result<Vec<u8>,UnCompressionError> {
+    let mut reader = data;
+    let mut sr = StreamReader::new(&mut reader);
+
+    let mut r = Vec::new();
+
+    {
+        let mut sw = StreamWriter::new(&mut r);
+
+        BlackHole::with_options(BlackHoleOptions::builder().decode_limits(limits).build()).uncompression(&mut sr,&mut sw)?;
+    }
+
+    Ok(r)
+}
 #[derive(Debug,Clone,Copy,PartialEq,Eq)]
 pub struct Score {
     word_len:usize,
-    count:usize
+    count:usize,
+    value:u128
 }
 impl Score {
     pub fn new(word_len:usize,count:usize) -> Score {
         Score {
             word_len,
-            count
+            count,
+            value: word_len as u128 * count as u128 - count as u128
+        }
+    }
+
+    pub fn with_model(word:&[u8],count:usize,context:&ScoreContext,model:&dyn ScoreModel) -> Score {
+        Score {
+            word_len: word.len(),
+            count,
+            value: model.value(word,count,context)
         }
     }
 
     pub fn value(&self) -> u128 {
-        self.word_len as u128 * self.count as u128 - self.count as u128
+        self.value
     }
 
     pub fn count(&self) -> usize {
@@ -43,11 +102,10 @@ impl Score {
 }
 impl Ord for Score {
     fn cmp(&self, other: &Self) -> Ordering {
-        (self.word_len as u128 * self.count as u128 - self.count as u128).cmp(
-            &(other.word_len as u128 * other.count as u128 - other.count as u128)
-        ).reverse()
+        self.value.cmp(&other.value).reverse()
     }
 }
+#[allow(clippy::needless_borrow)]
 impl PartialOrd for Score {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(&other))
@@ -61,30 +119,39 @@ pub struct Word {
 }
 impl Word {
     pub fn new(word:Vec<u8>, list: &[(usize,usize)], count:usize) -> Word {
-        let mut positions = BTreeSet::new();
+        let score = Score::new(word.len(),count);
 
-        for &(s,e) in list.iter() {
-            positions.insert((s,e));
-        }
+        Word::with_score(word,list,score)
+    }
 
-        let word_len = word.len();
+    pub fn with_score(word:Vec<u8>, list: &[(usize,usize)], score:Score) -> Word {
+        let positions = list.iter().cloned().collect::<BTreeSet<(usize,usize)>>();
 
         Word {
-            word: word,
-            score: Score::new(word_len,count),
-            positions: positions
+            word,
+            score,
+            positions
         }
     }
 
+    pub fn word(&self) -> &[u8] {
+        &self.word
+    }
+
     pub fn score(&self) -> Score {
         self.score
     }
+
+    pub fn positions(&self) -> &BTreeSet<(usize,usize)> {
+        &self.positions
+    }
 }
 impl Ord for Word {
     fn cmp(&self, other: &Self) -> Ordering {
         self.score.cmp(&other.score).then(self.word.cmp(&other.word).reverse())
     }
 }
+#[allow(clippy::needless_borrow)]
 impl PartialOrd for Word {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(&other))
@@ -97,36 +164,165 @@ impl PartialEq for Word {
 }
 impl Eq for Word {
 
+}
+type ParsedWords = (Vec<Vec<u8>>,HuffmanTree<Vec<u8>>);
+type DecodeTree = (HuffmanTree<Vec<u8>>,Option<HuffmanDecodeTable<Vec<u8>>>);
+type DecodeTreeRef<'a> = (&'a HuffmanTree<Vec<u8>>,Option<&'a HuffmanDecodeTable<Vec<u8>>>);
+pub(crate) struct DataDecoder {
+    stream_tree:Option<DecodeTree>,
+    unified:bool,
+    checksum:bool,
+    size:usize,
+    current_size:usize,
+    crc:Crc32,
+    finished:bool
+}
+impl DataDecoder {
+    pub(crate) fn is_finished(&self) -> bool {
+        self.finished
+    }
 }
 pub struct BlackHole {
-
+    options:BlackHoleOptions
 }
 impl BlackHole {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> BlackHole {
         BlackHole {
+            options: BlackHoleOptions::new()
+        }
+    }
 
+    pub fn with_options(options:BlackHoleOptions) -> BlackHole {
+        BlackHole {
+            options
         }
     }
 
+    pub fn options(&self) -> &BlackHoleOptions {
+        &self.options
+    }
+
     pub fn analysis<'a,'b,R>(&self,reader:&'a mut StreamReader<'b,R>)
         -> Result<(BTreeSet<Word>,usize),CompressionError> where R: Read + 'b {
+        let data = reader.read_to_end()?;
+
+        Ok((self.analysis_data(&data)?,data.len()))
+    }
+
+    pub fn analysis_data(&self,data:&[u8]) -> Result<BTreeSet<Word>,CompressionError> {
+        match self.options.analysis_method() {
+            AnalysisMethod::NGram => self.analysis_ngram(data,&ScoreContext::with_version(data,self.options.version())),
+            AnalysisMethod::SuffixArray => self.analysis_suffix_array(data,&ScoreContext::with_version(data,self.options.version()))
+        }
+    }
+
+    fn new_word(&self,word:Vec<u8>,list:&[(usize,usize)],count:usize,context:&ScoreContext) -> Word {
+        let score = Score::with_model(&word,count,context,self.options.score_model());
+
+        Word::with_score(word,list,score)
+    }
+
+    fn analysis_suffix_array(&self,data:&[u8],context:&ScoreContext) -> Result<BTreeSet<Word>,CompressionError> {
+        let len = data.len();
+
+        let min_count = self.options.min_count();
+        let max_word_size = self.options.max_word_size();
 
-        let mut data = Vec::new();
         let mut words = BTreeSet::new();
 
-        let mut list = Vec::new();
+        let mut singles = BTreeMap::new();
 
-        let mut i = 0;
+        for (i,&b) in data.iter().enumerate() {
+            singles.entry(b).or_insert(Vec::new()).push((i,i + 1));
+        }
 
-        {
-            while let Some(b) = reader.read_once()? {
-                data.push(b);
-                list.push((i,i+1));
+        for (b,list) in singles.into_iter() {
+            words.insert(self.new_word(vec![b], &list, len, context));
+        }
+
+        if len < 2 || max_word_size < 2 {
+            return Ok(words);
+        }
+
+        let sa = suffix_array(data);
+        let lcp = lcp_array(data,&sa);
+
+        let mut intervals = Vec::new();
+        let mut stack = vec![(0,0)];
+
+        for k in 1..=len {
+            let h = if k < len { lcp[k - 1] } else { 0 };
+
+            let mut lb = k - 1;
+
+            while h < stack[stack.len() - 1].0 {
+                let (l,b) = stack.pop().unwrap_or((0,0));
+
+                lb = b;
+
+                let parent = h.max(stack.last().map(|&(l,_)| l).unwrap_or(0));
 
-                i += 1;
+                intervals.push((parent,l,lb,k - 1));
+            }
+
+            if h > stack[stack.len() - 1].0 {
+                stack.push((h,lb));
+            }
+        }
+
+        if min_count <= 1 {
+            for k in 0..len {
+                let prev = if k > 0 { lcp[k - 1] } else { 0 };
+                let next = if k + 1 < len { lcp[k] } else { 0 };
+
+                intervals.push((prev.max(next),len - sa[k],k,k));
             }
         }
 
+        let found = intervals.into_par_iter().filter(|&(parent,l,lb,rb)| {
+            rb - lb + 1 >= min_count && (parent + 1).max(2) <= l.min(max_word_size)
+        }).map(|(parent,l,lb,rb)| {
+            let mut positions = sa[lb..=rb].to_vec();
+
+            positions.sort_unstable();
+
+            let mut found = Vec::new();
+
+            for word_len in (parent + 1).max(2)..=l.min(max_word_size) {
+                let mut count = 0;
+                let mut cr = 0;
+
+                for &p in positions.iter() {
+                    if cr <= p {
+                        count += 1;
+                        cr = p + word_len;
+                    }
+                }
+
+                if count < min_count {
+                    break;
+                }
+
+                let list = positions.iter().map(|&p| (p,p + word_len)).collect::<Vec<(usize,usize)>>();
+
+                found.push(self.new_word(data[positions[0]..(positions[0] + word_len)].to_vec(), &list, count, context));
+            }
+
+            found
+        }).flatten().collect::<Vec<Word>>();
+
+        words.extend(found);
+
+        Ok(words)
+    }
+
+    #[allow(clippy::redundant_closure)]
+    fn analysis_ngram(&self,data:&[u8],context:&ScoreContext) -> Result<BTreeSet<Word>,CompressionError> {
+        let mut words = BTreeSet::new();
+
+        let list = (0..data.len()).map(|i| (i,i+1)).collect::<Vec<(usize,usize)>>();
+
         let count = list.len();
 
         let mut dic = list.iter().fold(BTreeMap::new(), | mut acc, &(l,r) | {
@@ -135,18 +331,15 @@ impl BlackHole {
         });
 
         for (word,(list,count)) in dic.iter() {
-            words.insert(Word::new(word.clone(), list, *count));
+            words.insert(self.new_word(word.clone(), list, *count, context));
         }
 
-        let data = Arc::new(data);
         let len = data.len();
 
-        while dic.len() > 0 {
-            let data = Arc::clone(&data);
-
-            const MIN_COUNT:usize = 8;
-            const MAX_WORD_SIZE:usize = 64;
+        let min_count = self.options.min_count();
+        let max_word_size = self.options.max_word_size();
 
+        while !dic.is_empty() {
             let (d,mut w) = dic.into_par_iter()
                 .fold(|| (BTreeMap::new(),BTreeSet::new()), | (mut dic, mut words), (_, (list,_)) | {
 
@@ -183,7 +376,7 @@ impl BlackHole {
 
                     (k, (v, count))
                 }).filter(|(word,(_,count))| {
-                    *count >= MIN_COUNT && word.len() <= MAX_WORD_SIZE
+                    *count >= min_count && word.len() <= max_word_size
                 }).fold(|| BTreeMap::new(), | mut acc, (k,v) | {
                     acc.insert(k,v);
                     acc
@@ -193,7 +386,7 @@ impl BlackHole {
                 });
 
                 for (word, (list,count)) in d.iter() {
-                    words.insert(Word::new(word.clone(), list, *count));
+                    words.insert(self.new_word(word.clone(), list, *count, context));
                 }
 
                 dic.append(&mut d);
@@ -210,13 +403,144 @@ impl BlackHole {
             dic = d;
         }
 
-        Ok((words,data.len()))
+        Ok(words)
     }
 
+    #[allow(clippy::type_complexity)]
     pub fn build_words_and_tree<'a,'b>(&mut self,
                                        words:&'a BTreeSet<Word>,
                                        size:usize)
         -> Result<(Vec<Vec<u8>>,HuffmanTree<Vec<u8>>),CompressionError> where 'a: 'b {
+        let (seq,used_words) = self.select_words(words,size);
+
+        let huffman_tree = self.build_tree(used_words)?;
+
+        Ok((seq,huffman_tree))
+    }
+
+    fn build_tree(&self,used_words:Vec<(Vec<u8>,Score)>) -> Result<HuffmanTree<Vec<u8>>,CompressionError> {
+        if let Some(max_code_length) = self.options.max_code_length() {
+            HuffmanTree::length_limited(used_words,max_code_length)
+        } else if self.options.version() >= VERSION_CANONICAL_CODES {
+            HuffmanTree::canonical(used_words)
+        } else {
+            Ok(HuffmanTree::new(used_words))
+        }
+    }
+
+    pub fn optimal_parse(&self,words:&BTreeSet<Word>,data:&[u8],huffman_tree:&HuffmanTree<Vec<u8>>) -> Vec<Vec<u8>> {
+        let flag = if self.options.version() >= VERSION_UNIFIED_ALPHABET { 0 } else { 1 };
+
+        let literal_cost = |b:u8| huffman_tree.get_bits(&vec![b]).map(|bits| flag + bits.len()).unwrap_or(9);
+
+        let mut candidates = vec![Vec::new(); data.len()];
+
+        for w in words.iter().filter(|w| w.word.len() > 1) {
+            if let Some(bits) = huffman_tree.get_bits(&w.word) {
+                for &(s,_) in w.positions.iter() {
+                    candidates[s].push((w.word.len(),flag + bits.len()));
+                }
+            }
+        }
+
+        let mut costs = vec![usize::MAX; data.len() + 1];
+        let mut lens = vec![0; data.len() + 1];
+
+        costs[0] = 0;
+
+        for i in 0..data.len() {
+            let cost = costs[i] + literal_cost(data[i]);
+
+            if cost < costs[i + 1] {
+                costs[i + 1] = cost;
+                lens[i + 1] = 1;
+            }
+
+            for &(len,c) in candidates[i].iter() {
+                if costs[i] + c < costs[i + len] {
+                    costs[i + len] = costs[i] + c;
+                    lens[i + len] = len;
+                }
+            }
+        }
+
+        let mut seq = Vec::new();
+        let mut i = data.len();
+
+        while i > 0 {
+            seq.push(data[(i - lens[i])..i].to_vec());
+            i -= lens[i];
+        }
+
+        seq.reverse();
+        seq
+    }
+
+    fn build_parsed_tree(&self,seq:&[Vec<u8>]) -> Result<HuffmanTree<Vec<u8>>,CompressionError> {
+        let mut counts = BTreeMap::new();
+
+        for w in seq.iter() {
+            *counts.entry(w).or_insert(0) += 1;
+        }
+
+        let mut used_words = counts.into_iter().map(|(w,c)| (w.clone(),Score::new(w.len(),c))).collect::<Vec<(Vec<u8>,Score)>>();
+
+        used_words.sort_by(|(lw,ls),(rw,rs)| rs.count().cmp(&ls.count()).then(lw.cmp(rw)));
+        used_words.truncate(self.options.max_dictionary_size());
+
+        self.build_tree(used_words)
+    }
+
+    fn build_unified_tree(&self,seq:Vec<Vec<u8>>,huffman_tree:&HuffmanTree<Vec<u8>>) -> Result<ParsedWords,CompressionError> {
+        let seq = self.split_uncoded(seq,|w| huffman_tree.contains_word(w));
+
+        // Every literal has to stay codable, so words only get what is left of the budget.
+        let literals = seq.iter().flatten().collect::<BTreeSet<&u8>>().len();
+
+        let mut counts = BTreeMap::new();
+
+        for w in seq.iter().filter(|w| w.len() > 1) {
+            *counts.entry(w).or_insert(0) += 1;
+        }
+
+        let mut words = counts.into_iter().collect::<Vec<(&Vec<u8>,usize)>>();
+
+        words.sort_by(|(lw,lc),(rw,rc)| rc.cmp(lc).then(lw.cmp(rw)));
+        words.truncate(self.options.max_dictionary_size().saturating_sub(literals));
+
+        let kept = words.into_iter().map(|(w,_)| w.clone()).collect::<BTreeSet<Vec<u8>>>();
+
+        let seq = self.split_uncoded(seq,|w| kept.contains(w));
+
+        let mut counts = BTreeMap::new();
+
+        for w in seq.iter() {
+            *counts.entry(w).or_insert(0) += 1;
+        }
+
+        let used_words = counts.into_iter().map(|(w,c)| (w.clone(),Score::new(w.len(),c))).collect::<Vec<(Vec<u8>,Score)>>();
+
+        let huffman_tree = self.build_tree(used_words)?;
+
+        Ok((seq,huffman_tree))
+    }
+
+    fn split_uncoded<F>(&self,seq:Vec<Vec<u8>>,coded:F) -> Vec<Vec<u8>> where F: Fn(&Vec<u8>) -> bool {
+        let mut r = Vec::with_capacity(seq.len());
+
+        for w in seq.into_iter() {
+            if w.len() > 1 && !coded(&w) {
+                r.extend(w.into_iter().map(|b| vec![b]));
+            } else {
+                r.push(w);
+            }
+        }
+
+        r
+    }
+
+    #[allow(clippy::type_complexity,clippy::into_iter_on_ref,clippy::if_same_then_else,clippy::int_plus_one)]
+    pub fn select_words(&self,words:&BTreeSet<Word>,size:usize) -> (Vec<Vec<u8>>,Vec<(Vec<u8>,Score)>) {
         let mut seq = BTreeMap::new();
 
         let mut used_words = Vec::new();
@@ -227,6 +551,11 @@ impl BlackHole {
         let mut current_size = 0;
 
         'outer: for w in words.into_iter() {
+            if w.word.len() > 1 && (w.score.value() < self.options.min_score().max(1) ||
+                                    used_words.len() >= self.options.max_dictionary_size()) {
+                continue;
+            }
+
             let mut used_count = 0;
 
             for &(s,e) in w.positions.iter() {
@@ -252,25 +581,34 @@ impl BlackHole {
                 }
             }
 
-            if used_count > 0 {
+            if used_count > 0 && used_words.len() < self.options.max_dictionary_size() {
                 used_words.push((w.word.clone(),Score::new(w.word.len(),used_count)));
             }
         }
 
-        let huffman_tree = HuffmanTree::new(used_words);
-
         let mut r = Vec::new();
 
         for (_,w) in seq.into_iter() {
             r.push(w);
         }
-        Ok((r,huffman_tree))
+        (r,used_words)
     }
 
-    pub fn complete_compression<W>(&mut self,writer:&mut StreamWriter<'_,W>,
+    pub fn complete_compression<W>(&self,writer:&mut StreamWriter<'_,W>,
                                    words:Vec<Vec<u8>>,
-                                   huffman_tree:&mut HuffmanTree<Vec<u8>>)
+                                   huffman_tree:&HuffmanTree<Vec<u8>>)
         -> Result<(),CompressionError> where W: Write {
+        if self.options.version() >= VERSION_UNIFIED_ALPHABET {
+            for w in self.split_uncoded(words,|w| huffman_tree.contains_word(w)) {
+                huffman_tree.write(writer,w)?;
+            }
+
+            writer.pad_zeros()?;
+            writer.flush()?;
+
+            return Ok(());
+        }
+
         for w in words {
             if !huffman_tree.contains_word(&w) {
                 for &b in &w {
@@ -289,28 +627,55 @@ impl BlackHole {
         Ok(())
     }
 
-    pub fn compression<W,R>(&mut self,reader:&mut StreamReader<'_,R>,writer:&mut StreamWriter<'_,W>)
-        -> Result<(),CompressionError> where W: Write, R: Read {
-        let (words,size) = self.analysis(reader)?;
+    fn write_dictionary<W>(&self,writer:&mut StreamWriter<'_,W>,huffman_tree:&HuffmanTree<Vec<u8>>)
+        -> Result<(),CompressionError> where W: Write {
+        if self.options.version() >= VERSION_CANONICAL_CODES {
+            self.write_canonical_dictionary(writer,huffman_tree)
+        } else {
+            self.write_explicit_dictionary(writer,huffman_tree)
+        }
+    }
 
-        let (seq,mut huffman_tree) = self.build_words_and_tree(&words,size)?;
+    fn write_canonical_dictionary<W>(&self,writer:&mut StreamWriter<'_,W>,huffman_tree:&HuffmanTree<Vec<u8>>)
+        -> Result<(),CompressionError> where W: Write {
+        let lengths = huffman_tree.code_lengths();
 
-        let words = huffman_tree.words();
+        write_size(writer,lengths.len())?;
 
-        let dic_size = words.len();
+        if let Some(&(_,max_len)) = lengths.last() {
+            write_size(writer,max_len)?;
 
-        if dic_size < 1 << 6 {
-            writer.write((dic_size as u8) << 2)?;
-        } else if dic_size < 1 << 14 {
-            writer.write_u16(((dic_size as u16) << 2) | 0b01)?;
-        } else if dic_size < 1 << 30 {
-            writer.write_u32(((dic_size as u32) << 2) | 0b10)?;
-        } else if dic_size < 1 << 62 {
-            writer.write_u64(((dic_size as u64) << 2) | 0b11)?;
-        } else {
-            return Err(CompressionError::LimitError(String::from("Data size is too large.")))
+            let mut it = lengths.iter().peekable();
+
+            for len in 0..=max_len {
+                let mut words = Vec::new();
+
+                while let Some(&&(word,_)) = it.peek().filter(|&&&(_,l)| l == len) {
+                    words.push(word);
+                    it.next();
+                }
+
+                write_size(writer,words.len())?;
+
+                for word in words {
+                    write_size(writer,word.len())?;
+                    writer.write_bytes(word)?;
+                }
+            }
         }
 
+        writer.pad_zeros()?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    fn write_explicit_dictionary<W>(&self,writer:&mut StreamWriter<'_,W>,huffman_tree:&HuffmanTree<Vec<u8>>)
+        -> Result<(),CompressionError> where W: Write {
+        let words = huffman_tree.words();
+
+        write_size(writer,words.len())?;
+
         for word in words {
             let bits = huffman_tree.get_bits(word).ok_or(ReadError::UnexpectedEofError)?;
 
@@ -326,46 +691,81 @@ impl BlackHole {
 
             bits.write(writer)?;
 
-            let word_size = word.len();
-
-            if word_size < 1 << 6 {
-                writer.write((word_size as u8) << 2)?;
-            } else if word_size < 1 << 14 {
-                writer.write_u16(((word_size as u16) << 2) | 0b01)?;
-            } else if word_size < 1 << 30 {
-                writer.write_u32(((word_size as u32) << 2) | 0b10)?;
-            } else if word_size < 1 << 62 {
-                writer.write_u64(((word_size as u64) << 2) | 0b11)?;
-            } else {
-                return Err(CompressionError::LimitError(String::from("Data size is too large.")))
-            }
+            write_size(writer,word.len())?;
 
             writer.write_bytes(word)?;
         }
 
-        writer.write_u64(size as u64)?;
+        writer.pad_zeros()?;
+        writer.flush()?;
 
-        self.complete_compression(writer,seq,&mut huffman_tree)
+        Ok(())
     }
 
-    pub fn uncompression<R,W>(&mut self,reader:&mut StreamReader<'_,R>,writer:&mut StreamWriter<'_,W>)
-        -> Result<(),UnCompressionError> where R: Read, W: Write {
-        let h = reader.get_bits_from_lsb(2)?;
-
-        let dic_size = if h == 0b00 {
-            reader.get_bits_from_lsb(6)? as usize
-        } else if h == 0b01 {
-            (reader.get_bits_from_lsb(6)? as usize) | ((reader.read_u8()? as usize) << 6)
-        } else if h == 0b10 {
-            (reader.get_bits_from_lsb(6)? as usize) | ((reader.read_u8()? as usize) << 6) | ((reader.read_u16()? as usize) << 14)
-        } else if h == 0b11 {
-            (reader.get_bits_from_lsb(6)? as usize) |
-            ((reader.read_u8()? as usize) << 6) |
-            ((reader.read_u16()? as usize) << 14) |
-            ((reader.read_u32()? as usize) << 30)
+    fn read_dictionary<R>(&self,reader:&mut StreamReader<'_,R>,version:u8)
+        -> Result<HuffmanTree<Vec<u8>>,UnCompressionError> where R: Read {
+        if version >= VERSION_CANONICAL_CODES {
+            self.read_canonical_dictionary(reader)
         } else {
-            return Err(UnCompressionError::FormatError);
-        };
+            self.read_explicit_dictionary(reader)
+        }
+    }
+
+    fn read_canonical_dictionary<R>(&self,reader:&mut StreamReader<'_,R>) -> Result<HuffmanTree<Vec<u8>>,UnCompressionError> where R: Read {
+        let limits = self.options.decode_limits();
+
+        let dic_size = read_size(reader)?;
+
+        if dic_size > limits.max_dictionary_entries() {
+            return Err(UnCompressionError::LimitExceeded(String::from("The number of dictionary entries exceeds the limit.")));
+        }
+
+        let mut lengths = Vec::new();
+
+        if dic_size > 0 {
+            let max_len = read_size(reader)?;
+
+            if max_len > limits.max_code_length() {
+                return Err(UnCompressionError::LimitExceeded(String::from("The length of the Huffman code exceeds the limit.")));
+            } else if max_len > MAX_CANONICAL_CODE_LENGTH {
+                return Err(UnCompressionError::FormatError);
+            }
+
+            for len in 0..=max_len {
+                let count = read_size(reader)?;
+
+                if lengths.len() + count > dic_size {
+                    return Err(UnCompressionError::FormatError);
+                }
+
+                for _ in 0..count {
+                    let word_size = read_size(reader)?;
+
+                    if word_size > limits.max_word_size() {
+                        return Err(UnCompressionError::LimitExceeded(String::from("The size of the dictionary word exceeds the limit.")));
+                    }
+
+                    lengths.push((reader.read_until(word_size)?,len));
+                }
+            }
+
+            if lengths.len() != dic_size {
+                return Err(UnCompressionError::FormatError);
+            }
+        }
+
+        HuffmanTree::from_lengths(lengths)
+    }
+
+    #[allow(clippy::needless_bool)]
+    fn read_explicit_dictionary<R>(&self,reader:&mut StreamReader<'_,R>) -> Result<HuffmanTree<Vec<u8>>,UnCompressionError> where R: Read {
+        let limits = self.options.decode_limits();
+
+        let dic_size = read_size(reader)?;
+
+        if dic_size > limits.max_dictionary_entries() {
+            return Err(UnCompressionError::LimitExceeded(String::from("The number of dictionary entries exceeds the limit.")));
+        }
 
         let mut huffman_tree = HuffmanTree::empty();
 
@@ -378,6 +778,10 @@ impl BlackHole {
                 reader.get_bits_from_lsb(7)? as usize | (reader.read_u8()? as usize) << 7
             };
 
+            if huffman_code_size > limits.max_code_length() {
+                return Err(UnCompressionError::LimitExceeded(String::from("The length of the Huffman code exceeds the limit.")));
+            }
+
             let mut code = Bits::new();
 
             for _ in 0..huffman_code_size {
@@ -388,50 +792,630 @@ impl BlackHole {
                 });
             }
 
-            let h = reader.get_bits_from_lsb(2)?;
+            let word_size = read_size(reader)?;
 
-            let word_size = if h == 0b00 {
-                reader.get_bits_from_lsb(6)? as usize
-            } else if h == 0b01 {
-                (reader.get_bits_from_lsb(6)? as usize) | ((reader.read_u8()? as usize) << 6)
-            } else if h == 0b10 {
-                (reader.get_bits_from_lsb(6)? as usize) | ((reader.read_u8()? as usize) << 6) | ((reader.read_u16()? as usize) << 14)
-            } else if h == 0b11 {
-                (reader.get_bits_from_lsb(6)? as usize) |
-                ((reader.read_u8()? as usize) << 6) |
-                ((reader.read_u16()? as usize) << 14) |
-                ((reader.read_u32()? as usize) << 30)
-            } else {
-                return Err(UnCompressionError::FormatError);
-            };
+            if word_size > limits.max_word_size() {
+                return Err(UnCompressionError::LimitExceeded(String::from("The size of the dictionary word exceeds the limit.")));
+            }
 
             let word = reader.read_until(word_size)?;
 
             huffman_tree.insert(word,code)?;
         }
 
-        let size = reader.read_u64()? as usize;
+        Ok(huffman_tree)
+    }
 
-        let mut current_size = 0;
+    fn flags(&self) -> u8 {
+        let mut flags = 0;
 
-        while current_size < size {
-            let h = reader.get_bit_from_lsb()?;
+        if self.options.content_checksum() {
+            flags |= FLAG_CONTENT_CHECKSUM;
+        }
 
-            if h == 0b0 {
-                let word = huffman_tree.find_word(reader)?;
-                current_size += word.len();
+        if self.options.header_checksum() {
+            flags |= FLAG_HEADER_CHECKSUM;
+        }
 
-                writer.write_bytes(word)?;
-            } else if h == 0b1 {
-                current_size += 1;
+        if self.options.block_size().is_some() {
+            flags |= FLAG_BLOCKS;
+        }
+
+        if self.options.seek_index() {
+            flags |= FLAG_BLOCKS | FLAG_SEEK_INDEX;
+        }
+
+        if self.options.dictionary().is_some() {
+            flags |= FLAG_DICTIONARY;
+        }
+
+        flags
+    }
+
+    fn compress_data<W>(&mut self,writer:&mut StreamWriter<'_,W>,data:&[u8]) -> Result<(),CompressionError> where W: Write {
+        if !(MIN_VERSION..=VERSION).contains(&self.options.version()) {
+            return Err(CompressionError::InvalidState(format!("Unsupported format version. (version = {})",self.options.version())));
+        }
+
+        if let Some(dictionary) = self.options.dictionary() {
+            let words = dictionary.find_words(data);
 
-                writer.write(reader.read_u8()?)?;
+            let huffman_tree = if self.options.version() >= VERSION_UNIFIED_ALPHABET {
+                dictionary.unified_tree()
             } else {
-                return Err(UnCompressionError::FormatError);
+                dictionary.tree()
             };
-        }
 
-        writer.flush()?;
+            let seq = if self.options.optimal_parse_iterations().is_some() {
+                self.optimal_parse(&words,data,huffman_tree)
+            } else {
+                self.select_words(&words,data.len()).0
+            };
+
+            writer.write_u32(dictionary.id())?;
+
+            self.complete_compression(writer,seq,huffman_tree)?;
+        } else {
+            let words = self.analysis_data(data)?;
+
+            let (mut seq,mut huffman_tree) = self.build_words_and_tree(&words,data.len())?;
+
+            for _ in 0..self.options.optimal_parse_iterations().unwrap_or(0) {
+                seq = self.optimal_parse(&words,data,&huffman_tree);
+                huffman_tree = self.build_parsed_tree(&seq)?;
+            }
+
+            if self.options.version() >= VERSION_UNIFIED_ALPHABET {
+                (seq,huffman_tree) = self.build_unified_tree(seq,&huffman_tree)?;
+            }
+
+            let mut dic = Vec::new();
+
+            {
+                let mut dw = StreamWriter::new(&mut dic);
+
+                self.write_dictionary(&mut dw,&huffman_tree)?;
+            }
+
+            write_size(writer,dic.len())?;
+            writer.write_bytes(&dic)?;
+
+            if self.options.header_checksum() {
+                writer.write_u32(crc32(&dic))?;
+            }
+
+            self.complete_compression(writer,seq,&huffman_tree)?;
+        }
+
+        if self.options.content_checksum() {
+            writer.write_u32(crc32(data))?;
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn compress_block<W>(&mut self,writer:&mut StreamWriter<'_,W>,data:&[u8]) -> Result<usize,CompressionError> where W: Write {
+        let mut body = Vec::new();
+
+        {
+            let mut bw = StreamWriter::new(&mut body);
+
+            self.compress_data(&mut bw,data)?;
+        }
+
+        if body.len() >= data.len() + self.stored_checksum_size() {
+            return self.store_block(writer,data);
+        }
+
+        let block_header = BlockHeader::new(BLOCK_COMPRESSED,data.len(),body.len());
+
+        block_header.write(writer)?;
+        writer.write_bytes(&body)?;
+        writer.flush()?;
+
+        Ok(block_header.encoded_size() + body.len())
+    }
+
+    fn stored_checksum_size(&self) -> usize {
+        if self.options.content_checksum() { 4 } else { 0 }
+    }
+
+    fn store_block<W>(&self,writer:&mut StreamWriter<'_,W>,data:&[u8]) -> Result<usize,CompressionError> where W: Write {
+        let block_header = BlockHeader::new(BLOCK_STORED,data.len(),data.len() + self.stored_checksum_size());
+
+        block_header.write(writer)?;
+        writer.write_bytes(data)?;
+
+        if self.options.content_checksum() {
+            writer.write_u32(crc32(data))?;
+        }
+
+        writer.flush()?;
+
+        Ok(block_header.encoded_size() + block_header.compressed_size())
+    }
+
+    fn finish_blocks<W>(&self,writer:&mut StreamWriter<'_,W>,index:&BlockIndex,offset:u64) -> Result<(),CompressionError> where W: Write {
+        BlockHeader::end().write(writer)?;
+
+        if self.options.seek_index() {
+            index.write(writer,offset + 1)?;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn compression<W,R>(&mut self,reader:&mut StreamReader<'_,R>,writer:&mut StreamWriter<'_,W>)
+        -> Result<(),CompressionError> where W: Write, R: Read {
+        if self.flags() & FLAG_BLOCKS != 0 {
+            let block_size = self.options.block_size().unwrap_or(DEFAULT_BLOCK_SIZE);
+
+            if block_size == 0 {
+                return Err(CompressionError::InvalidState(String::from("The block size must be greater than zero.")));
+            }
+
+            Header::new(self.options.version(),self.flags(),SIZE_UNKNOWN).write(writer)?;
+
+            let mut index = BlockIndex::new();
+            let mut offset = HEADER_SIZE as u64;
+
+            loop {
+                let data = reader.read_upto(block_size)?;
+
+                if data.is_empty() {
+                    break;
+                }
+
+                index.push(offset,data.len() as u64);
+
+                offset += self.compress_block(writer,&data)? as u64;
+            }
+
+            self.finish_blocks(writer,&index,offset)?;
+        } else {
+            let data = reader.read_to_end()?;
+
+            let mut body = Vec::new();
+
+            {
+                let mut bw = StreamWriter::new(&mut body);
+
+                self.compress_data(&mut bw,&data)?;
+            }
+
+            let stored = BlockHeader::new(BLOCK_STORED,data.len(),data.len() + self.stored_checksum_size());
+
+            if body.len() > stored.encoded_size() + stored.compressed_size() + BlockHeader::end().encoded_size() {
+                Header::new(self.options.version(),self.flags() | FLAG_BLOCKS,data.len() as u64).write(writer)?;
+
+                self.store_block(writer,&data)?;
+
+                BlockHeader::end().write(writer)?;
+            } else {
+                Header::new(self.options.version(),self.flags(),data.len() as u64).write(writer)?;
+
+                writer.write_bytes(&body)?;
+            }
+
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn uncompress_block<R,W>(&mut self,reader:&mut StreamReader<'_,R>,writer:&mut StreamWriter<'_,W>,
+                                        header:&Header,block_header:&BlockHeader)
+        -> Result<(),UnCompressionError> where R: Read, W: Write {
+        self.uncompress_block_with_info(reader,writer,header,block_header,&mut BlockInfo::new())
+    }
+
+    pub(crate) fn uncompress_block_with_info<R,W>(&mut self,reader:&mut StreamReader<'_,R>,writer:&mut StreamWriter<'_,W>,
+                                                  header:&Header,block_header:&BlockHeader,info:&mut BlockInfo)
+        -> Result<(),UnCompressionError> where R: Read, W: Write {
+        if !block_header.is_stored() {
+            return self.uncompress_data_with_info(reader,writer,header,block_header.size(),info);
+        }
+
+        let checksum_size = if header.has_flag(FLAG_CONTENT_CHECKSUM) { 4 } else { 0 };
+
+        if block_header.compressed_size() != block_header.size() + checksum_size {
+            return Err(UnCompressionError::FormatError);
+        }
+
+        let data = reader.read_until(block_header.size())?;
+
+        if header.has_flag(FLAG_CONTENT_CHECKSUM) {
+            let expected = reader.read_u32()?;
+            let actual = crc32(&data);
+
+            if expected != actual {
+                return Err(UnCompressionError::ChecksumMismatch { expected, actual });
+            }
+        }
+
+        info.set_stored();
+
+        writer.write_bytes(&data)?;
+
+        Ok(())
+    }
+
+    fn uncompress_data<R,W>(&mut self,reader:&mut StreamReader<'_,R>,writer:&mut StreamWriter<'_,W>,
+                            header:&Header,size:usize)
+        -> Result<(),UnCompressionError> where R: Read, W: Write {
+        self.uncompress_data_with_info(reader,writer,header,size,&mut BlockInfo::new())
+    }
+
+    fn uncompress_data_with_info<R,W>(&mut self,reader:&mut StreamReader<'_,R>,writer:&mut StreamWriter<'_,W>,
+                                      header:&Header,size:usize,info:&mut BlockInfo)
+        -> Result<(),UnCompressionError> where R: Read, W: Write {
+        let mut data = self.begin_data(reader,header,size,info)?;
+
+        self.decode_data(&mut data,reader,writer,info,usize::MAX)
+    }
+
+    pub(crate) fn begin_data<R>(&self,reader:&mut StreamReader<'_,R>,header:&Header,size:usize,info:&mut BlockInfo)
+        -> Result<DataDecoder,UnCompressionError> where R: Read {
+        let start = reader.bit_position();
+
+        let mut shared_dictionary = None;
+        let mut stream_tree = None;
+
+        if header.has_flag(FLAG_DICTIONARY) {
+            let id = reader.read_u32()?;
+
+            self.options.dictionary()
+                        .filter(|d| d.id() == id)
+                        .ok_or(UnCompressionError::DictionaryMismatch(id))?;
+
+            shared_dictionary = Some(id);
+        } else {
+            let dic_size = read_size(reader)?;
+            let dic = reader.read_until(dic_size)?;
+
+            if header.has_flag(FLAG_HEADER_CHECKSUM) {
+                let expected = reader.read_u32()?;
+                let actual = crc32(&dic);
+
+                if expected != actual {
+                    return Err(UnCompressionError::ChecksumMismatch { expected, actual });
+                }
+            }
+
+            let tree = {
+                let mut dr = dic.deref();
+                let mut dr = StreamReader::new(&mut dr);
+
+                self.read_dictionary(&mut dr,header.version())?
+            };
+
+            let table = HuffmanDecodeTable::new(&tree);
+
+            stream_tree = Some((tree,table));
+        }
+
+        let data = DataDecoder {
+            stream_tree,
+            unified: header.version() >= VERSION_UNIFIED_ALPHABET,
+            checksum: header.has_flag(FLAG_CONTENT_CHECKSUM),
+            size,
+            current_size: 0,
+            crc: Crc32::new(),
+            finished: false
+        };
+
+        let words = if info.collect_words() {
+            let (huffman_tree,_) = self.data_tree(&data.stream_tree,data.unified)?;
+
+            huffman_tree.words().into_iter().filter_map(|w| huffman_tree.get_bits(w).map(|bits| (w.clone(),bits.clone()))).collect()
+        } else {
+            Vec::new()
+        };
+
+        info.set_dictionary(((reader.bit_position() - start) / 8) as usize,shared_dictionary,words);
+
+        Ok(data)
+    }
+
+    fn data_tree<'a>(&'a self,stream_tree:&'a Option<DecodeTree>,unified:bool)
+        -> Result<DecodeTreeRef<'a>,UnCompressionError> {
+        if let Some((tree,table)) = stream_tree.as_ref() {
+            return Ok((tree,table.as_ref()));
+        }
+
+        let dictionary = self.options.dictionary().ok_or(UnCompressionError::InvalidState(String::from("The shared dictionary is missing.")))?;
+
+        if unified {
+            Ok((dictionary.unified_tree(),dictionary.unified_table()))
+        } else {
+            Ok((dictionary.tree(),dictionary.table()))
+        }
+    }
+
+    pub(crate) fn decode_data<R,W>(&self,data:&mut DataDecoder,reader:&mut StreamReader<'_,R>,writer:&mut StreamWriter<'_,W>,
+                                   info:&mut BlockInfo,limit:usize)
+        -> Result<(),UnCompressionError> where R: Read, W: Write {
+        let (huffman_tree,table) = self.data_tree(&data.stream_tree,data.unified)?;
+
+        let size = data.size;
+        let start_size = data.current_size;
+
+        while data.current_size < size && data.current_size - start_size < limit {
+            let h = if data.unified { 0b0 } else { reader.get_bit_from_lsb()? };
+
+            if h == 0b0 {
+                let word = if let Some(table) = table {
+                    table.find_word(reader)?
+                } else {
+                    huffman_tree.find_word(reader)?
+                };
+                data.current_size += word.len();
+
+                if data.current_size > size {
+                    return Err(UnCompressionError::FormatError);
+                }
+
+                if word.len() == 1 {
+                    info.add_literal();
+                } else {
+                    info.add_word(word.len());
+                }
+
+                data.crc.update(word);
+                writer.write_bytes(word)?;
+            } else if h == 0b1 {
+                data.current_size += 1;
+
+                let b = reader.read_u8()?;
+
+                info.add_literal();
+
+                data.crc.update(&[b]);
+                writer.write(b)?;
+            } else {
+                return Err(UnCompressionError::FormatError);
+            };
+        }
+
+        if data.current_size < size || data.finished {
+            return Ok(());
+        }
+
+        data.finished = true;
+
+        if data.checksum {
+            reader.align_to_byte();
+
+            let expected = reader.read_u32()?;
+            let actual = data.crc.value();
+
+            if expected != actual {
+                return Err(UnCompressionError::ChecksumMismatch { expected, actual });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_output_size(&self,size:u64) -> Result<(),UnCompressionError> {
+        if size != SIZE_UNKNOWN && size > self.options.decode_limits().max_output_size() {
+            Err(UnCompressionError::LimitExceeded(String::from("The decompressed data exceeds the output size limit.")))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn uncompression<R,W>(&mut self,reader:&mut StreamReader<'_,R>,writer:&mut StreamWriter<'_,W>)
+        -> Result<(),UnCompressionError> where R: Read, W: Write {
+        let header = Header::read(reader)?;
+
+        self.check_output_size(header.size())?;
+
+        if header.has_flag(FLAG_BLOCKS) {
+            let mut total_size = 0;
+
+            loop {
+                let block_header = BlockHeader::read(reader)?;
+
+                if block_header.is_end() {
+                    break;
+                }
+
+                self.check_output_size(total_size + block_header.size() as u64)?;
+
+                let body = reader.read_until(block_header.compressed_size())?;
+
+                let mut br = body.deref();
+                let mut br = StreamReader::new(&mut br);
+
+                self.uncompress_block(&mut br,writer,&header,&block_header)?;
+
+                writer.flush()?;
+
+                total_size += block_header.size() as u64;
+            }
+
+            if header.size() != SIZE_UNKNOWN && header.size() != total_size {
+                return Err(UnCompressionError::FormatError);
+            }
+
+            self.read_index(reader,&header,total_size)?;
+        } else {
+            self.uncompress_data(reader,writer,&header,header.size() as usize)?;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    fn read_index<R>(&self,reader:&mut StreamReader<'_,R>,header:&Header,total_size:u64) -> Result<(),UnCompressionError> where R: Read {
+        if header.has_flag(FLAG_SEEK_INDEX) {
+            let (index,_) = BlockIndex::read(reader)?;
+
+            if index.total_size() != total_size {
+                return Err(UnCompressionError::FormatError);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn thread_pool(&self) -> Result<ThreadPool,ThreadPoolBuildError> {
+        let mut builder = ThreadPoolBuilder::new();
+
+        if let Some(threads) = self.options.threads() {
+            builder = builder.num_threads(threads);
+        }
+
+        builder.build()
+    }
+
+    pub fn compress_parallel<W,R>(&mut self,reader:&mut StreamReader<'_,R>,writer:&mut StreamWriter<'_,W>)
+        -> Result<(),CompressionError> where W: Write, R: Read {
+        let block_size = self.options.block_size().unwrap_or(DEFAULT_BLOCK_SIZE);
+
+        if block_size == 0 {
+            return Err(CompressionError::InvalidState(String::from("The block size must be greater than zero.")));
+        }
+
+        let pool = self.thread_pool().map_err(|e| CompressionError::InvalidState(e.to_string()))?;
+        let batch_size = pool.current_num_threads();
+
+        Header::new(self.options.version(),self.flags() | FLAG_BLOCKS,SIZE_UNKNOWN).write(writer)?;
+
+        let mut index = BlockIndex::new();
+        let mut offset = HEADER_SIZE as u64;
+
+        loop {
+            let mut chunks = Vec::with_capacity(batch_size);
+
+            while chunks.len() < batch_size {
+                let data = reader.read_upto(block_size)?;
+
+                if data.is_empty() {
+                    break;
+                }
+
+                chunks.push(data);
+            }
+
+            if chunks.is_empty() {
+                break;
+            }
+
+            let options = &self.options;
+
+            let blocks = pool.install(|| {
+                chunks.par_iter().map(|data| {
+                    let mut bh = BlackHole::with_options(options.clone());
+
+                    let mut block = Vec::new();
+
+                    {
+                        let mut bw = StreamWriter::new(&mut block);
+
+                        bh.compress_block(&mut bw,data)?;
+                    }
+
+                    Ok(block)
+                }).collect::<Result<Vec<Vec<u8>>,CompressionError>>()
+            })?;
+
+            for (block,data) in blocks.iter().zip(chunks.iter()) {
+                index.push(offset,data.len() as u64);
+
+                offset += block.len() as u64;
+
+                writer.write_bytes(block)?;
+            }
+
+            writer.flush()?;
+
+            if chunks.len() < batch_size {
+                break;
+            }
+        }
+
+        self.finish_blocks(writer,&index,offset)?;
+
+        Ok(())
+    }
+
+    pub fn decompress_parallel<R,W>(&mut self,reader:&mut StreamReader<'_,R>,writer:&mut StreamWriter<'_,W>)
+        -> Result<(),UnCompressionError> where R: Read, W: Write {
+        let header = Header::read(reader)?;
+
+        self.check_output_size(header.size())?;
+
+        if !header.has_flag(FLAG_BLOCKS) {
+            self.uncompress_data(reader,writer,&header,header.size() as usize)?;
+            writer.flush()?;
+
+            return Ok(());
+        }
+
+        let pool = self.thread_pool().map_err(|e| UnCompressionError::InvalidState(e.to_string()))?;
+        let batch_size = pool.current_num_threads();
+
+        let mut total_size = 0u64;
+        let mut end = false;
+
+        while !end {
+            let mut bodies = Vec::with_capacity(batch_size);
+
+            while bodies.len() < batch_size {
+                let block_header = BlockHeader::read(reader)?;
+
+                if block_header.is_end() {
+                    end = true;
+                    break;
+                }
+
+                total_size = total_size.checked_add(block_header.size() as u64).ok_or(UnCompressionError::FormatError)?;
+
+                self.check_output_size(total_size)?;
+
+                bodies.push((block_header,reader.read_until(block_header.compressed_size())?));
+            }
+
+            let options = &self.options;
+
+            let blocks = pool.install(|| {
+                bodies.par_iter().map(|(block_header,body)| {
+                    let mut bh = BlackHole::with_options(options.clone());
+
+                    let mut block = Vec::with_capacity(block_header.size().min(DEFAULT_BLOCK_SIZE));
+
+                    {
+                        let mut br = body.deref();
+                        let mut br = StreamReader::new(&mut br);
+                        let mut bw = StreamWriter::new(&mut block);
+
+                        bh.uncompress_block(&mut br,&mut bw,&header,block_header)?;
+
+                        bw.flush()?;
+                    }
+
+                    Ok(block)
+                }).collect::<Result<Vec<Vec<u8>>,UnCompressionError>>()
+            })?;
+
+            for block in blocks {
+                writer.write_bytes(&block)?;
+            }
+
+            writer.flush()?;
+        }
+
+        if header.size() != SIZE_UNKNOWN && header.size() != total_size {
+            return Err(UnCompressionError::FormatError);
+        }
+
+        self.read_index(reader,&header,total_size)?;
 
         Ok(())
     }
@@ -19,6 +19,7 @@ fn gcd(a:u128,b:u128) -> u128 {
 }
 impl Fraction {
     #[inline]
+    #[allow(clippy::redundant_field_names)]
     pub fn new(n:u64) -> Fraction {
         Fraction {
             n:n,
@@ -0,0 +1,295 @@
+use std::sync::Arc;
+
+use crate::dictionary::Dictionary;
+use crate::format::VERSION;
+use crate::huffman::MAX_CANONICAL_CODE_LENGTH;
+use crate::score::{DefaultScoreModel, ScoreModel};
+
+pub const DEFAULT_BLOCK_SIZE:usize = 1024 * 1024;
+pub const DEFAULT_MAX_OUTPUT_SIZE:u64 = 1 << 32;
+pub const DEFAULT_MAX_DICTIONARY_ENTRIES:usize = 1 << 20;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum AnalysisMethod {
+    NGram,
+    SuffixArray
+}
+
+#[derive(Debug,Clone)]
+pub struct BlackHoleOptions {
+    min_count:usize,
+    max_word_size:usize,
+    max_dictionary_size:usize,
+    min_score:u128,
+    content_checksum:bool,
+    header_checksum:bool,
+    block_size:Option<usize>,
+    seek_index:bool,
+    threads:Option<usize>,
+    decode_limits:DecodeLimits,
+    version:u8,
+    max_code_length:Option<usize>,
+    dictionary:Option<Arc<Dictionary>>,
+    analysis_method:AnalysisMethod,
+    optimal_parse_iterations:Option<usize>,
+    score_model:Arc<dyn ScoreModel>
+}
+impl BlackHoleOptions {
+    pub fn new() -> BlackHoleOptions {
+        BlackHoleOptions {
+            min_count: 8,
+            max_word_size: 64,
+            max_dictionary_size: usize::MAX,
+            min_score: 0,
+            content_checksum: true,
+            header_checksum: true,
+            block_size: None,
+            seek_index: false,
+            threads: None,
+            decode_limits: DecodeLimits::new(),
+            version: VERSION,
+            max_code_length: None,
+            dictionary: None,
+            analysis_method: AnalysisMethod::NGram,
+            optimal_parse_iterations: None,
+            score_model: Arc::new(DefaultScoreModel)
+        }
+    }
+
+    pub fn builder() -> BlackHoleOptionsBuilder {
+        BlackHoleOptionsBuilder {
+            options: BlackHoleOptions::new()
+        }
+    }
+
+    pub fn min_count(&self) -> usize {
+        self.min_count
+    }
+
+    pub fn max_word_size(&self) -> usize {
+        self.max_word_size
+    }
+
+    pub fn max_dictionary_size(&self) -> usize {
+        self.max_dictionary_size
+    }
+
+    pub fn min_score(&self) -> u128 {
+        self.min_score
+    }
+
+    pub fn content_checksum(&self) -> bool {
+        self.content_checksum
+    }
+
+    pub fn header_checksum(&self) -> bool {
+        self.header_checksum
+    }
+
+    pub fn block_size(&self) -> Option<usize> {
+        self.block_size
+    }
+
+    pub fn seek_index(&self) -> bool {
+        self.seek_index
+    }
+
+    pub fn threads(&self) -> Option<usize> {
+        self.threads
+    }
+
+    pub fn decode_limits(&self) -> &DecodeLimits {
+        &self.decode_limits
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn max_code_length(&self) -> Option<usize> {
+        self.max_code_length
+    }
+
+    pub fn dictionary(&self) -> Option<&Dictionary> {
+        self.dictionary.as_deref()
+    }
+
+    pub fn analysis_method(&self) -> AnalysisMethod {
+        self.analysis_method
+    }
+
+    pub fn optimal_parse_iterations(&self) -> Option<usize> {
+        self.optimal_parse_iterations
+    }
+
+    pub fn score_model(&self) -> &dyn ScoreModel {
+        self.score_model.as_ref()
+    }
+}
+impl Default for BlackHoleOptions {
+    fn default() -> Self {
+        BlackHoleOptions::new()
+    }
+}
+#[derive(Debug,Clone)]
+pub struct BlackHoleOptionsBuilder {
+    options:BlackHoleOptions
+}
+impl BlackHoleOptionsBuilder {
+    pub fn min_count(mut self,min_count:usize) -> BlackHoleOptionsBuilder {
+        self.options.min_count = min_count;
+        self
+    }
+
+    pub fn max_word_size(mut self,max_word_size:usize) -> BlackHoleOptionsBuilder {
+        self.options.max_word_size = max_word_size;
+        self
+    }
+
+    pub fn max_dictionary_size(mut self,max_dictionary_size:usize) -> BlackHoleOptionsBuilder {
+        self.options.max_dictionary_size = max_dictionary_size;
+        self
+    }
+
+    pub fn min_score(mut self,min_score:u128) -> BlackHoleOptionsBuilder {
+        self.options.min_score = min_score;
+        self
+    }
+
+    pub fn content_checksum(mut self,enabled:bool) -> BlackHoleOptionsBuilder {
+        self.options.content_checksum = enabled;
+        self
+    }
+
+    pub fn header_checksum(mut self,enabled:bool) -> BlackHoleOptionsBuilder {
+        self.options.header_checksum = enabled;
+        self
+    }
+
+    pub fn block_size(mut self,block_size:usize) -> BlackHoleOptionsBuilder {
+        self.options.block_size = Some(block_size);
+        self
+    }
+
+    pub fn seek_index(mut self,enabled:bool) -> BlackHoleOptionsBuilder {
+        self.options.seek_index = enabled;
+        self
+    }
+
+    pub fn threads(mut self,threads:usize) -> BlackHoleOptionsBuilder {
+        self.options.threads = Some(threads);
+        self
+    }
+
+    pub fn decode_limits(mut self,decode_limits:DecodeLimits) -> BlackHoleOptionsBuilder {
+        self.options.decode_limits = decode_limits;
+        self
+    }
+
+    pub fn version(mut self,version:u8) -> BlackHoleOptionsBuilder {
+        self.options.version = version;
+        self
+    }
+
+    pub fn max_code_length(mut self,max_code_length:usize) -> BlackHoleOptionsBuilder {
+        self.options.max_code_length = Some(max_code_length);
+        self
+    }
+
+    pub fn dictionary(mut self,dictionary:Arc<Dictionary>) -> BlackHoleOptionsBuilder {
+        self.options.dictionary = Some(dictionary);
+        self
+    }
+
+    pub fn analysis_method(mut self,analysis_method:AnalysisMethod) -> BlackHoleOptionsBuilder {
+        self.options.analysis_method = analysis_method;
+        self
+    }
+
+    pub fn optimal_parse_iterations(mut self,iterations:usize) -> BlackHoleOptionsBuilder {
+        self.options.optimal_parse_iterations = Some(iterations);
+        self
+    }
+
+    pub fn score_model(mut self,score_model:Arc<dyn ScoreModel>) -> BlackHoleOptionsBuilder {
+        self.options.score_model = score_model;
+        self
+    }
+
+    pub fn build(self) -> BlackHoleOptions {
+        self.options
+    }
+}
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct DecodeLimits {
+    max_output_size:u64,
+    max_dictionary_entries:usize,
+    max_word_size:usize,
+    max_code_length:usize
+}
+impl DecodeLimits {
+    pub fn new() -> DecodeLimits {
+        DecodeLimits {
+            max_output_size: DEFAULT_MAX_OUTPUT_SIZE,
+            max_dictionary_entries: DEFAULT_MAX_DICTIONARY_ENTRIES,
+            max_word_size: usize::MAX,
+            max_code_length: MAX_CANONICAL_CODE_LENGTH
+        }
+    }
+
+    pub fn builder() -> DecodeLimitsBuilder {
+        DecodeLimitsBuilder {
+            limits: DecodeLimits::new()
+        }
+    }
+
+    pub fn max_output_size(&self) -> u64 {
+        self.max_output_size
+    }
+
+    pub fn max_dictionary_entries(&self) -> usize {
+        self.max_dictionary_entries
+    }
+
+    pub fn max_word_size(&self) -> usize {
+        self.max_word_size
+    }
+
+    pub fn max_code_length(&self) -> usize {
+        self.max_code_length
+    }
+}
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        DecodeLimits::new()
+    }
+}
+#[derive(Debug,Clone,Copy)]
+pub struct DecodeLimitsBuilder {
+    limits:DecodeLimits
+}
+impl DecodeLimitsBuilder {
+    pub fn max_output_size(mut self,max_output_size:u64) -> DecodeLimitsBuilder {
+        self.limits.max_output_size = max_output_size;
+        self
+    }
+
+    pub fn max_dictionary_entries(mut self,max_dictionary_entries:usize) -> DecodeLimitsBuilder {
+        self.limits.max_dictionary_entries = max_dictionary_entries;
+        self
+    }
+
+    pub fn max_word_size(mut self,max_word_size:usize) -> DecodeLimitsBuilder {
+        self.limits.max_word_size = max_word_size;
+        self
+    }
+
+    pub fn max_code_length(mut self,max_code_length:usize) -> DecodeLimitsBuilder {
+        self.limits.max_code_length = max_code_length;
+        self
+    }
+
+    pub fn build(self) -> DecodeLimits {
+        self.limits
+    }
+}
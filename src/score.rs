@@ -0,0 +1,84 @@
+use std::fmt::Debug;
+
+use crate::format::{encoded_size_len, VERSION, VERSION_UNIFIED_ALPHABET};
+
+#[derive(Debug,Clone)]
+pub struct ScoreContext {
+    size:usize,
+    flag_bits:f64,
+    literal_bits:[f64; 256]
+}
+impl ScoreContext {
+    pub fn new(data:&[u8]) -> ScoreContext {
+        ScoreContext::with_version(data,VERSION)
+    }
+
+    pub fn with_version(data:&[u8],version:u8) -> ScoreContext {
+        let flag_bits = if version >= VERSION_UNIFIED_ALPHABET { 0.0 } else { 1.0 };
+
+        let mut frequencies = [0usize; 256];
+
+        for &b in data.iter() {
+            frequencies[b as usize] += 1;
+        }
+
+        let mut literal_bits = [flag_bits + 8.0; 256];
+
+        for (bits,&f) in literal_bits.iter_mut().zip(frequencies.iter()) {
+            if f > 0 {
+                *bits = flag_bits + (data.len() as f64 / f as f64).log2().max(1.0);
+            }
+        }
+
+        ScoreContext {
+            size: data.len(),
+            flag_bits,
+            literal_bits
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn flag_bits(&self) -> f64 {
+        self.flag_bits
+    }
+
+    pub fn literal_bits(&self,b:u8) -> f64 {
+        self.literal_bits[b as usize]
+    }
+}
+pub trait ScoreModel: Debug + Send + Sync {
+    fn value(&self,word:&[u8],count:usize,context:&ScoreContext) -> u128;
+}
+#[derive(Debug,Clone,Copy,Default)]
+pub struct DefaultScoreModel;
+impl ScoreModel for DefaultScoreModel {
+    fn value(&self,word:&[u8],count:usize,_:&ScoreContext) -> u128 {
+        word.len() as u128 * count as u128 - count as u128
+    }
+}
+#[derive(Debug,Clone,Copy,Default)]
+pub struct EstimatedBitsModel;
+impl EstimatedBitsModel {
+    pub fn code_bits(&self,count:usize,context:&ScoreContext) -> f64 {
+        context.flag_bits() + (context.size() as f64 / count.max(1) as f64).log2().max(1.0)
+    }
+
+    pub fn entry_bits(&self,word:&[u8],count:usize,context:&ScoreContext) -> f64 {
+        ((encoded_size_len(word.len()) + word.len()) * 8) as f64 + self.code_bits(count,context)
+    }
+}
+impl ScoreModel for EstimatedBitsModel {
+    fn value(&self,word:&[u8],count:usize,context:&ScoreContext) -> u128 {
+        if word.len() <= 1 {
+            return 0;
+        }
+
+        let replaced = word.iter().map(|&b| context.literal_bits(b)).sum::<f64>() * count as f64;
+        let cost = self.code_bits(count,context) * count as f64 + self.entry_bits(word,count,context);
+
+        (replaced - cost).max(0.0) as u128
+    }
+}
@@ -1,89 +1,164 @@
+use std::io;
 use std::io::{Read, Write};
 use crate::error::{ReadError, WriteError};
 
+pub const MAX_PEEK_BITS:usize = 57;
+
 pub struct StreamReader<'a,R> where R: Read +'a {
     reader:&'a mut R,
     buf: [u8; 256],
     buf_size: usize,
     current_index:usize,
-    current_bits:usize
+    bit_buf:u64,
+    bit_count:usize,
+    total_read:u64,
+    eof:bool
 }
 impl<'a,R> StreamReader<'a,R> where R: Read +'a {
+    #[allow(clippy::redundant_field_names)]
     pub fn new(reader:&'a mut R) -> StreamReader<'a,R> {
         StreamReader {
             reader: reader,
             buf: [0;256],
             buf_size: 0,
             current_index:0,
-            current_bits:0
+            bit_buf:0,
+            bit_count:0,
+            total_read:0,
+            eof:false
         }
     }
 
     fn read_next(&mut self) -> Result<usize,ReadError> {
-        self.buf_size = self.reader.read(&mut self.buf)?;
-
+        self.buf_size = 0;
         self.current_index = 0;
 
+        while !self.eof {
+            match self.reader.read(&mut self.buf) {
+                Ok(0) => {
+                    self.eof = true;
+                },
+                Ok(size) => {
+                    self.buf_size = size;
+                    self.total_read += size as u64;
+                    break;
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => {
+                    return Err(ReadError::from(e));
+                }
+            }
+        }
+
         Ok(self.buf_size)
     }
-    
-    pub fn get_bit_from_lsb(&mut self) -> Result<u8,ReadError> {
-        if self.buf_size == 0 || self.current_index >= self.buf.len() {
-            if self.read_next()? == 0 {
-                Err(ReadError::UnexpectedEofError)
-            } else {
-                self.get_bit_from_lsb()
-            }
+
+    pub fn remaining_in_buffer(&self) -> usize {
+        self.buf_size - self.current_index + self.bit_count / 8
+    }
+
+    pub fn is_eof(&mut self) -> Result<bool,ReadError> {
+        if self.bit_count > 0 || self.current_index < self.buf_size {
+            Ok(false)
         } else {
-            let bit = if self.buf[self.current_index] & (1u8 << self.current_bits as u8) == 0 {
-                0
-            } else {
-                1
-            };
+            Ok(self.read_next()? == 0)
+        }
+    }
+
+    fn refill(&mut self) -> Result<(),ReadError> {
+        while self.bit_count <= 56 {
+            if self.bit_count < 56 && self.current_index + 8 <= self.buf_size {
+                let mut bytes = [0; 8];
+
+                bytes.copy_from_slice(&self.buf[self.current_index..(self.current_index + 8)]);
 
-            self.current_bits += 1;
+                let size = (63 - self.bit_count) / 8;
 
-            if self.current_bits >= 8 {
+                self.bit_buf |= u64::from_le_bytes(bytes) << self.bit_count;
+                self.current_index += size;
+                self.bit_count += size * 8;
+
+                self.bit_buf &= (1u64 << self.bit_count) - 1;
+            } else if self.current_index < self.buf_size {
+                self.bit_buf |= (self.buf[self.current_index] as u64) << self.bit_count;
                 self.current_index += 1;
-                self.current_bits = 0;
+                self.bit_count += 8;
+            } else if self.read_next()? == 0 {
+                break;
             }
+        }
+
+        Ok(())
+    }
+
+    pub fn peek_bits(&mut self,size:usize) -> Result<u64,ReadError> {
+        if size > MAX_PEEK_BITS {
+            return Err(ReadError::InvalidArgumentError(String::from("The specified peek size is too long.")));
+        }
 
-            Ok(bit)
+        if self.bit_count < size {
+            self.refill()?;
         }
+
+        Ok(self.bit_buf & ((1u64 << size) - 1))
+    }
+
+    pub fn consume(&mut self,size:usize) -> Result<(),ReadError> {
+        if size > MAX_PEEK_BITS {
+            return Err(ReadError::InvalidArgumentError(String::from("The specified consume size is too long.")));
+        }
+
+        if self.bit_count < size {
+            self.refill()?;
+
+            if self.bit_count < size {
+                return Err(ReadError::UnexpectedEofError);
+            }
+        }
+
+        self.bit_buf >>= size;
+        self.bit_count -= size;
+
+        Ok(())
+    }
+
+    pub fn read_bits(&mut self,size:usize) -> Result<u64,ReadError> {
+        let bits = self.peek_bits(size)?;
+
+        self.consume(size)?;
+
+        Ok(bits)
+    }
+
+    pub fn get_bit_from_lsb(&mut self) -> Result<u8,ReadError> {
+        Ok(self.read_bits(1)? as u8)
     }
 
     pub fn get_bits_from_lsb(&mut self, size:usize) -> Result<u8,ReadError> {
         if size > 8 {
             Err(ReadError::InvalidArgumentError(String::from("The specified read size is too long.")))
         } else {
-            let mut bits = 0;
-
-            for i in 0..size {
-                bits |= self.get_bit_from_lsb()? << i;
-            }
-
-            Ok(bits)
+            Ok(self.read_bits(size)? as u8)
         }
     }
 
     pub fn read_once(&mut self) -> Result<Option<u8>,ReadError> {
-        if self.buf_size == 0 || self.current_index >= self.buf_size {
-            if self.read_next()? == 0 {
+        if self.bit_count == 0 {
+            if self.current_index >= self.buf_size && self.read_next()? == 0 {
                 return Ok(None);
             }
-        }
 
-        if self.current_bits == 0 {
             let r = self.buf[self.current_index];
 
             self.current_index += 1;
 
             Ok(Some(r))
         } else {
-            self.get_bits_from_lsb(8).map(|b| Some(b))
+            self.read_bits(8).map(|b| Some(b as u8))
         }
     }
 
+    #[allow(clippy::needless_question_mark)]
     pub fn read_u8(&mut self) -> Result<u8,ReadError> {
         Ok(self.read_once()?.ok_or(ReadError::UnexpectedEofError)?)
     }
@@ -107,7 +182,7 @@ impl<'a,R> StreamReader<'a,R> where R: Read +'a {
     }
 
     pub fn read_until(&mut self,size:usize) -> Result<Vec<u8>,ReadError> {
-        let mut r = Vec::with_capacity(size);
+        let mut r = Vec::with_capacity(size.min(self.buf.len()));
 
         for _ in 0..size {
             r.push(self.read_once()?.ok_or(ReadError::UnexpectedEofError)?);
@@ -116,36 +191,130 @@ impl<'a,R> StreamReader<'a,R> where R: Read +'a {
         Ok(r)
     }
 
+    pub fn read_upto(&mut self,size:usize) -> Result<Vec<u8>,ReadError> {
+        let mut r = Vec::new();
+
+        while r.len() < size {
+            if let Some(b) = self.read_once()? {
+                r.push(b);
+            } else {
+                break;
+            }
+        }
+
+        Ok(r)
+    }
+
+    pub fn read_to_end(&mut self) -> Result<Vec<u8>,ReadError> {
+        let mut r = Vec::new();
+
+        while let Some(b) = self.read_once()? {
+            r.push(b);
+        }
+
+        Ok(r)
+    }
+
+    pub fn bit_position(&self) -> u64 {
+        (self.total_read - (self.buf_size - self.current_index) as u64) * 8 - self.bit_count as u64
+    }
+
     pub fn skip_bits(&mut self,count:usize) -> Result<(),ReadError> {
-        self.current_index += count / 8;
-        self.current_bits += count % 8;
+        if count < self.bit_count {
+            self.bit_buf >>= count;
+            self.bit_count -= count;
 
-        Ok(())
+            return Ok(());
+        }
+
+        let mut count = count - self.bit_count;
+
+        self.bit_buf = 0;
+        self.bit_count = 0;
+
+        while count >= 8 {
+            if self.current_index >= self.buf_size && self.read_next()? == 0 {
+                return Err(ReadError::UnexpectedEofError);
+            }
+
+            let size = (count / 8).min(self.buf_size - self.current_index);
+
+            self.current_index += size;
+
+            count -= size * 8;
+        }
+
+        self.consume(count)
+    }
+
+    pub fn align_to_byte(&mut self) {
+        let surplus = self.bit_count % 8;
+
+        self.bit_buf >>= surplus;
+        self.bit_count -= surplus;
     }
 
+    #[deprecated(note = "use align_to_byte")]
     pub fn skip_surplus_bits(&mut self) {
-        self.current_index += 1;
-        self.current_bits = 0;
+        self.align_to_byte();
+    }
+
+    pub(crate) fn with_state(reader:&'a mut R,state:StreamReaderState) -> StreamReader<'a,R> {
+        StreamReader {
+            reader,
+            buf: state.buf,
+            buf_size: state.buf_size,
+            current_index: state.current_index,
+            bit_buf: state.bit_buf,
+            bit_count: state.bit_count,
+            total_read: state.total_read,
+            eof: state.eof
+        }
+    }
+
+    pub(crate) fn into_state(self) -> StreamReaderState {
+        StreamReaderState {
+            buf: self.buf,
+            buf_size: self.buf_size,
+            current_index: self.current_index,
+            bit_buf: self.bit_buf,
+            bit_count: self.bit_count,
+            total_read: self.total_read,
+            eof: self.eof
+        }
     }
 }
+pub(crate) struct StreamReaderState {
+    buf: [u8; 256],
+    buf_size: usize,
+    current_index:usize,
+    bit_buf:u64,
+    bit_count:usize,
+    total_read:u64,
+    eof:bool
+}
 pub struct StreamWriter<'a,W> where W: Write +'a {
     writer:&'a mut W,
     buf: [u8; 256],
     current_index:usize,
-    current_bits:usize,
+    bit_buf:u64,
+    bit_count:usize,
     written_size:usize
 }
 impl<'a,W> StreamWriter<'a,W> where W: Write +'a {
+    #[allow(clippy::redundant_field_names)]
     pub fn new(writer:&'a mut W) -> StreamWriter<'a,W> {
         StreamWriter {
             writer:writer,
             buf: [0;256],
             current_index:0,
-            current_bits:0,
+            bit_buf:0,
+            bit_count:0,
             written_size:0
         }
     }
 
+    #[allow(clippy::unnecessary_mut_passed,clippy::needless_borrow)]
     pub fn flush(&mut self) -> Result<(),WriteError> {
         let mut slice = &mut self.buf[0..self.current_index];
         let mut start_index = 0;
@@ -164,53 +333,32 @@ impl<'a,W> StreamWriter<'a,W> where W: Write +'a {
             slice = &mut slice[size..];
         }
 
-        if self.current_bits == 0 {
-            self.current_index = 0;
-            self.current_bits = 0;
-        } else {
-            self.buf[0] = self.buf[self.current_index];
-            self.current_index = 0;
-        };
+        self.current_index = 0;
 
         Ok(())
     }
 
-    pub fn write(&mut self, b:u8) -> Result<(),WriteError> {
-        if self.current_index >= 256 {
+    fn push(&mut self,b:u8) -> Result<(),WriteError> {
+        if self.current_index >= self.buf.len() {
             self.flush()?;
         }
 
-        if self.current_bits == 0 {
-            self.buf[self.current_index] = b;
-            self.current_index += 1;
+        self.buf[self.current_index] = b;
+        self.current_index += 1;
 
-            Ok(())
+        Ok(())
+    }
+
+    pub fn write(&mut self, b:u8) -> Result<(),WriteError> {
+        if self.bit_count == 0 {
+            self.push(b)
         } else {
-            self.write_u8_to_bits(b)
+            self.write_bits(b as u64,8)
         }
     }
 
     pub fn write_bit(&mut self,b:bool) -> Result<(),WriteError> {
-        if self.current_index >= 256 {
-            self.flush()?;
-        }
-
-        if self.current_bits == 0 {
-            self.buf[self.current_index] = 0;
-        }
-
-        if b {
-            self.buf[self.current_index] |= 1u8 << self.current_bits;
-        }
-
-        self.current_bits += 1;
-
-        if self.current_bits >= 8 {
-            self.current_index += 1;
-            self.current_bits = 0;
-        }
-
-        Ok(())
+        self.write_bits(b as u64,1)
     }
 
     pub fn write_u16(&mut self,value:u16) -> Result<(),WriteError> {
@@ -236,39 +384,42 @@ impl<'a,W> StreamWriter<'a,W> where W: Write +'a {
         Ok(())
     }
 
-    fn write_u8_to_bits(&mut self,value:u8) -> Result<(),WriteError> {
-        for i in 0..8 {
-            if value & (1u8 << i) != 0 {
-                self.write_bit(true)?;
-            } else {
-                self.write_bit(false)?;
-            }
+    pub fn write_bits(&mut self,value:u64,len:usize) -> Result<(),WriteError> {
+        if len > MAX_PEEK_BITS {
+            self.write_bits(value & 0xFFFFFFFF,32)?;
+
+            return self.write_bits(value >> 32,len - 32);
         }
 
-        Ok(())
-    }
+        self.bit_buf |= (value & ((1u64 << len) - 1)) << self.bit_count;
+        self.bit_count += len;
 
-    pub fn write_bits(&mut self,value:u64,len:usize) -> Result<(),WriteError> {
-        for i in 0..len {
-            if value & (1u64 << i) != 0 {
-                self.write_bit(true)?;
-            } else {
-                self.write_bit(false)?;
-            }
+        while self.bit_count >= 8 {
+            let b = self.bit_buf as u8;
+
+            self.push(b)?;
+
+            self.bit_buf >>= 8;
+            self.bit_count -= 8;
         }
 
         Ok(())
     }
 
     pub fn pad_zeros(&mut self) -> Result<(),WriteError> {
-        if self.current_bits > 0 {
-            self.current_bits = 0;
-            self.current_index += 1;
+        if self.bit_count > 0 {
+            let b = self.bit_buf as u8;
+
+            self.push(b)?;
+
+            self.bit_buf = 0;
+            self.bit_count = 0;
         }
 
         Ok(())
     }
 
+    #[allow(clippy::into_iter_on_ref)]
     pub fn write_bytes(&mut self,bytes:&[u8]) -> Result<(),WriteError> {
         for &b in bytes.into_iter() {
             self.write(b)?;
@@ -0,0 +1,179 @@
+const EMPTY:usize = usize::MAX;
+
+fn sa_is(s:&[usize],upper:usize) -> Vec<usize> {
+    let n = s.len();
+
+    match n {
+        0 => return Vec::new(),
+        1 => return vec![0],
+        2 => return if s[0] < s[1] { vec![0,1] } else { vec![1,0] },
+        _ => ()
+    }
+
+    let mut sa = vec![EMPTY; n];
+    let mut ls = vec![false; n];
+
+    for i in (0..(n - 1)).rev() {
+        ls[i] = if s[i] == s[i + 1] { ls[i + 1] } else { s[i] < s[i + 1] };
+    }
+
+    let mut sum_l = vec![0; upper + 1];
+    let mut sum_s = vec![0; upper + 1];
+
+    for i in 0..n {
+        if !ls[i] {
+            sum_s[s[i]] += 1;
+        } else {
+            sum_l[s[i] + 1] += 1;
+        }
+    }
+
+    for i in 0..=upper {
+        sum_s[i] += sum_l[i];
+
+        if i < upper {
+            sum_l[i + 1] += sum_s[i];
+        }
+    }
+
+    let induce = |sa:&mut Vec<usize>,lms:&[usize]| {
+        sa.iter_mut().for_each(|v| *v = EMPTY);
+
+        let mut buf = sum_s.clone();
+
+        for &d in lms.iter() {
+            if d == n {
+                continue;
+            }
+
+            sa[buf[s[d]]] = d;
+            buf[s[d]] += 1;
+        }
+
+        let mut buf = sum_l.clone();
+
+        sa[buf[s[n - 1]]] = n - 1;
+        buf[s[n - 1]] += 1;
+
+        for i in 0..n {
+            let v = sa[i];
+
+            if v != EMPTY && v >= 1 && !ls[v - 1] {
+                sa[buf[s[v - 1]]] = v - 1;
+                buf[s[v - 1]] += 1;
+            }
+        }
+
+        let mut buf = sum_l.clone();
+
+        for i in (0..n).rev() {
+            let v = sa[i];
+
+            if v != EMPTY && v >= 1 && ls[v - 1] {
+                buf[s[v - 1] + 1] -= 1;
+                sa[buf[s[v - 1] + 1]] = v - 1;
+            }
+        }
+    };
+
+    let mut lms_map = vec![EMPTY; n + 1];
+    let mut lms = Vec::new();
+
+    for i in 1..n {
+        if !ls[i - 1] && ls[i] {
+            lms_map[i] = lms.len();
+            lms.push(i);
+        }
+    }
+
+    let m = lms.len();
+
+    induce(&mut sa,&lms);
+
+    if m > 0 {
+        let mut sorted_lms = sa.iter().cloned().filter(|&v| lms_map[v] != EMPTY).collect::<Vec<usize>>();
+
+        let mut rec_s = vec![0; m];
+        let mut rec_upper = 0;
+
+        rec_s[lms_map[sorted_lms[0]]] = 0;
+
+        for i in 1..m {
+            let mut l = sorted_lms[i - 1];
+            let mut r = sorted_lms[i];
+
+            let end_l = if lms_map[l] + 1 < m { lms[lms_map[l] + 1] } else { n };
+            let end_r = if lms_map[r] + 1 < m { lms[lms_map[r] + 1] } else { n };
+
+            let mut same = true;
+
+            if end_l - l != end_r - r {
+                same = false;
+            } else {
+                while l < end_l && s[l] == s[r] {
+                    l += 1;
+                    r += 1;
+                }
+
+                if l == n || s[l] != s[r] {
+                    same = false;
+                }
+            }
+
+            if !same {
+                rec_upper += 1;
+            }
+
+            rec_s[lms_map[sorted_lms[i]]] = rec_upper;
+        }
+
+        let rec_sa = sa_is(&rec_s,rec_upper);
+
+        for i in 0..m {
+            sorted_lms[i] = lms[rec_sa[i]];
+        }
+
+        induce(&mut sa,&sorted_lms);
+    }
+
+    sa
+}
+pub fn suffix_array(data:&[u8]) -> Vec<usize> {
+    let s = data.iter().map(|&b| b as usize).collect::<Vec<usize>>();
+
+    sa_is(&s,255)
+}
+pub fn lcp_array(data:&[u8],sa:&[usize]) -> Vec<usize> {
+    let n = data.len();
+
+    if n == 0 {
+        return Vec::new();
+    }
+
+    let mut rank = vec![0; n];
+
+    for (i,&p) in sa.iter().enumerate() {
+        rank[p] = i;
+    }
+
+    let mut lcp = vec![0; n - 1];
+    let mut h:usize = 0;
+
+    for i in 0..n {
+        h = h.saturating_sub(1);
+
+        if rank[i] == 0 {
+            continue;
+        }
+
+        let j = sa[rank[i] - 1];
+
+        while j + h < n && i + h < n && data[j + h] == data[i + h] {
+            h += 1;
+        }
+
+        lcp[rank[i] - 1] = h;
+    }
+
+    lcp
+}
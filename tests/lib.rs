@@ -1,10 +1,24 @@
 extern crate blackhole_compress;
 
+use std::cell::Cell;
 use std::fs::File;
-use std::io::{Read,BufReader};
+use std::io;
+use std::collections::BTreeMap;
+use std::io::{Read,BufReader,Cursor,Seek,SeekFrom,Write};
+use std::process::{Command, Stdio};
 use std::ops::Deref;
-use blackhole_compress::BlackHole;
-use blackhole_compress::stream::{StreamReader, StreamWriter};
+use std::sync::Arc;
+use blackhole_compress::{BlackHole, compress, decompress, decompress_with_limit, Score};
+use blackhole_compress::huffman::{HuffmanDecodeTable, HuffmanTree, PRIMARY_TABLE_BITS};
+use blackhole_compress::checksum::crc32;
+use blackhole_compress::codec::{BlackHoleDecoder, BlackHoleEncoder, BlackHoleSeekableReader};
+use blackhole_compress::dictionary::Dictionary;
+use blackhole_compress::options::{AnalysisMethod, BlackHoleOptions, DecodeLimits};
+use blackhole_compress::error::{ReadError, UnCompressionError};
+use blackhole_compress::format::{BLOCK_COMPRESSED, BLOCK_STORED, BlockHeader, encoded_size_len, FLAG_BLOCKS, FLAG_CONTENT_CHECKSUM, FLAG_SEEK_INDEX, FOOTER_SIZE, Header, HEADER_SIZE, INDEX_ENTRY_SIZE, MAGIC, MIN_VERSION, SIZE_UNKNOWN, VERSION, VERSION_CANONICAL_CODES, VERSION_EXPLICIT_CODES, VERSION_UNIFIED_ALPHABET, write_size};
+use blackhole_compress::stream::{MAX_PEEK_BITS, StreamReader, StreamWriter};
+use blackhole_compress::score::{DefaultScoreModel, EstimatedBitsModel, ScoreContext, ScoreModel};
+use blackhole_compress::suffix_array::{lcp_array, suffix_array};
 
 #[test]
 fn compression_and_uncompression() {
@@ -43,4 +57,1579 @@ fn compression_and_uncompression() {
     //println!("{}",String::from_utf8_lossy(&uncompress));
 
     assert_eq!(original,uncompress);
-}
\ No newline at end of file
+}
+
+#[test]
+fn uncompression_rejects_foreign_input() {
+    let input = b"this is not a blackhole stream".to_vec();
+
+    let mut i = input.deref();
+    let mut sr = StreamReader::new(&mut i);
+
+    let mut o = Vec::new();
+    let mut sw = StreamWriter::new(&mut o);
+
+    let mut bh = BlackHole::new();
+
+    match bh.uncompression(&mut sr,&mut sw) {
+        Err(UnCompressionError::NotBlackHoleStream) => (),
+        r => panic!("unexpected result {:?}",r)
+    }
+}
+
+#[test]
+fn uncompression_rejects_unsupported_version() {
+    let input = b"abababababababababababababababab".to_vec();
+
+    let mut i = input.deref();
+    let mut sr = StreamReader::new(&mut i);
+
+    let mut compressed = Vec::new();
+    let mut sw = StreamWriter::new(&mut compressed);
+
+    let mut bh = BlackHole::new();
+
+    bh.compression(&mut sr,&mut sw).unwrap();
+
+    compressed[MAGIC.len()] = VERSION + 1;
+
+    let mut i = compressed.deref();
+    let mut sr = StreamReader::new(&mut i);
+
+    let mut o = Vec::new();
+    let mut sw = StreamWriter::new(&mut o);
+
+    match bh.uncompression(&mut sr,&mut sw) {
+        Err(UnCompressionError::UnsupportedVersion(v)) if v == VERSION + 1 => (),
+        r => panic!("unexpected result {:?}",r)
+    }
+}
+
+#[test]
+fn crc32_check_value() {
+    assert_eq!(crc32(b"123456789"),0xCBF43926);
+}
+
+#[test]
+fn uncompression_detects_corrupted_data() {
+    let input = b"abababababababababababababababab".to_vec();
+
+    let mut i = input.deref();
+    let mut sr = StreamReader::new(&mut i);
+
+    let mut compressed = Vec::new();
+    let mut sw = StreamWriter::new(&mut compressed);
+
+    let mut bh = BlackHole::new();
+
+    bh.compression(&mut sr,&mut sw).unwrap();
+
+    let mut corrupted = compressed.clone();
+    let len = corrupted.len();
+
+    corrupted[len - 1] ^= 0x01;
+
+    let mut i = corrupted.deref();
+    let mut sr = StreamReader::new(&mut i);
+
+    let mut o = Vec::new();
+    let mut sw = StreamWriter::new(&mut o);
+
+    match bh.uncompression(&mut sr,&mut sw) {
+        Err(UnCompressionError::ChecksumMismatch { .. }) => (),
+        r => panic!("unexpected result {:?}",r)
+    }
+
+    let mut corrupted = compressed.clone();
+    let header_size = MAGIC.len() + 10;
+
+    corrupted[header_size + 1] ^= 0x80;
+
+    let mut i = corrupted.deref();
+    let mut sr = StreamReader::new(&mut i);
+
+    let mut o = Vec::new();
+    let mut sw = StreamWriter::new(&mut o);
+
+    match bh.uncompression(&mut sr,&mut sw) {
+        Err(UnCompressionError::ChecksumMismatch { .. }) => (),
+        r => panic!("unexpected result {:?}",r)
+    }
+}
+
+fn compress_with(bh:&mut BlackHole,input:&[u8]) -> Vec<u8> {
+    let mut i = input;
+    let mut sr = StreamReader::new(&mut i);
+
+    let mut compressed = Vec::new();
+    let mut sw = StreamWriter::new(&mut compressed);
+
+    bh.compression(&mut sr,&mut sw).unwrap();
+
+    compressed
+}
+
+fn uncompress_with(bh:&mut BlackHole,input:&[u8]) -> Vec<u8> {
+    let mut i = input;
+    let mut sr = StreamReader::new(&mut i);
+
+    let mut o = Vec::new();
+    let mut sw = StreamWriter::new(&mut o);
+
+    bh.uncompression(&mut sr,&mut sw).unwrap();
+
+    o
+}
+
+#[test]
+fn compression_with_options() {
+    let input = b"the quick brown fox jumps over the lazy dog. the quick brown fox jumps again.".to_vec();
+
+    let options = [
+        BlackHoleOptions::builder().min_count(2).build(),
+        BlackHoleOptions::builder().min_count(2).max_word_size(4).build(),
+        BlackHoleOptions::builder().min_count(2).max_dictionary_size(8).build(),
+        BlackHoleOptions::builder().min_count(2).max_dictionary_size(0).build(),
+        BlackHoleOptions::builder().min_count(2).min_score(20).build(),
+        BlackHoleOptions::builder().content_checksum(false).header_checksum(false).build()
+    ];
+
+    for o in options.iter() {
+        let mut bh = BlackHole::with_options(o.clone());
+
+        let compressed = compress_with(&mut bh,&input);
+
+        assert_eq!(input,uncompress_with(&mut bh,&compressed));
+    }
+}
+
+fn testdata(size:usize) -> Vec<u8> {
+    let mut reader = BufReader::new(File::open("testdata/legal_moves.rs").unwrap());
+
+    let mut data = Vec::new();
+
+    reader.read_to_end(&mut data).unwrap();
+
+    data.truncate(size);
+
+    data
+}
+
+#[test]
+fn compression_with_blocks() {
+    let input = testdata(32 * 1024 + 100);
+
+    let mut bh = BlackHole::with_options(BlackHoleOptions::builder().block_size(8 * 1024).build());
+
+    let compressed = compress_with(&mut bh,&input);
+
+    assert!(compressed.len() < input.len());
+
+    assert_eq!(input,uncompress_with(&mut BlackHole::new(),&compressed));
+
+    let compressed = compress_with(&mut bh,&[]);
+
+    assert_eq!(Vec::<u8>::new(),uncompress_with(&mut bh,&compressed));
+}
+
+#[test]
+fn parallel_compression_and_uncompression() {
+    let input = testdata(40 * 1024);
+
+    let mut bh = BlackHole::with_options(BlackHoleOptions::builder().block_size(8 * 1024).threads(2).build());
+
+    let mut i = input.deref();
+    let mut sr = StreamReader::new(&mut i);
+
+    let mut compressed = Vec::new();
+    let mut sw = StreamWriter::new(&mut compressed);
+
+    bh.compress_parallel(&mut sr,&mut sw).unwrap();
+
+    assert_eq!(input,uncompress_with(&mut BlackHole::new(),&compressed));
+
+    let mut i = compressed.deref();
+    let mut sr = StreamReader::new(&mut i);
+
+    let mut o = Vec::new();
+    let mut sw = StreamWriter::new(&mut o);
+
+    bh.decompress_parallel(&mut sr,&mut sw).unwrap();
+
+    assert_eq!(input,o);
+}
+
+#[test]
+fn parallel_decompression_rejects_oversized_block_header() {
+    let mut crafted = Vec::new();
+
+    {
+        let mut sw = StreamWriter::new(&mut crafted);
+
+        Header::new(VERSION,FLAG_BLOCKS,SIZE_UNKNOWN).write(&mut sw).unwrap();
+
+        for _ in 0..10 {
+            BlockHeader::new(BLOCK_COMPRESSED,1 << 61,4).write(&mut sw).unwrap();
+            sw.write_bytes(&[0; 4]).unwrap();
+        }
+
+        BlockHeader::end().write(&mut sw).unwrap();
+        sw.flush().unwrap();
+    }
+
+    for threads in [1,4] {
+        let mut i = crafted.deref();
+        let mut sr = StreamReader::new(&mut i);
+
+        let mut o = Vec::new();
+        let mut sw = StreamWriter::new(&mut o);
+
+        let r = BlackHole::with_options(BlackHoleOptions::builder().threads(threads).build()).decompress_parallel(&mut sr,&mut sw);
+
+        assert!(r.is_err());
+    }
+}
+
+fn block_stream(flags:u8,size:u64,blocks:&[(BlockHeader,&[u8])]) -> Vec<u8> {
+    let mut r = Vec::new();
+
+    {
+        let mut sw = StreamWriter::new(&mut r);
+
+        Header::new(VERSION,flags,size).write(&mut sw).unwrap();
+
+        for (block_header,body) in blocks.iter() {
+            block_header.write(&mut sw).unwrap();
+            sw.write_bytes(body).unwrap();
+        }
+
+        BlockHeader::end().write(&mut sw).unwrap();
+        sw.flush().unwrap();
+    }
+
+    r
+}
+
+#[test]
+fn uncompression_rejects_crafted_streams() {
+    let data = b"hello";
+
+    let mut body = data.to_vec();
+
+    body.extend_from_slice(&crc32(data).to_le_bytes());
+
+    let stored = BlockHeader::new(BLOCK_STORED,data.len(),body.len());
+    let flags = FLAG_BLOCKS | FLAG_CONTENT_CHECKSUM;
+
+    assert_eq!(data.to_vec(),decompress(&block_stream(flags,data.len() as u64,&[(stored,&body)])).unwrap());
+    assert_eq!(data.to_vec(),decompress(&block_stream(flags,SIZE_UNKNOWN,&[(stored,&body)])).unwrap());
+
+    let mut corrupted = body.clone();
+
+    corrupted[0] ^= 0x01;
+
+    match decompress(&block_stream(flags,SIZE_UNKNOWN,&[(stored,&corrupted)])) {
+        Err(UnCompressionError::ChecksumMismatch { .. }) => (),
+        r => panic!("unexpected result {:?}",r)
+    }
+
+    let crafted = [
+        block_stream(flags | 0x80,SIZE_UNKNOWN,&[(stored,&body)]),
+        block_stream(FLAG_CONTENT_CHECKSUM,SIZE_UNKNOWN,&[]),
+        block_stream(FLAG_SEEK_INDEX,0,&[]),
+        block_stream(flags,data.len() as u64 + 1,&[(stored,&body)]),
+        block_stream(flags,SIZE_UNKNOWN,&[(BlockHeader::new(3,data.len(),body.len()),&body)]),
+        block_stream(flags,SIZE_UNKNOWN,&[(BlockHeader::new(BLOCK_STORED,data.len(),data.len()),&data[..])]),
+        block_stream(FLAG_BLOCKS,SIZE_UNKNOWN,&[(stored,&body)])
+    ];
+
+    for c in crafted.iter() {
+        match decompress(c) {
+            Err(UnCompressionError::FormatError) => (),
+            r => panic!("unexpected result {:?}",r)
+        }
+    }
+
+    let mut crafted = block_stream(flags,SIZE_UNKNOWN,&[]);
+
+    crafted[MAGIC.len()] = MIN_VERSION - 1;
+
+    match decompress(&crafted) {
+        Err(UnCompressionError::UnsupportedVersion(0)) => (),
+        r => panic!("unexpected result {:?}",r)
+    }
+
+    let input = testdata(4 * 1024);
+
+    let options = BlackHoleOptions::builder().block_size(1024).seek_index(true).build();
+    let compressed = compress_with(&mut BlackHole::with_options(options),&input);
+
+    let index_start = compressed.len() - FOOTER_SIZE - 8 - 4 * INDEX_ENTRY_SIZE;
+
+    for position in [compressed.len() - 1,index_start,index_start + 8 + INDEX_ENTRY_SIZE + 8] {
+        let mut corrupted = compressed.clone();
+
+        corrupted[position] ^= 0x01;
+
+        match decompress(&corrupted) {
+            Err(UnCompressionError::FormatError) => (),
+            Err(UnCompressionError::ReadError(ReadError::UnexpectedEofError)) if position == index_start => (),
+            r => panic!("unexpected result {:?}",r)
+        }
+
+        assert!(BlackHoleDecoder::new(&corrupted[..]).read_to_end(&mut Vec::new()).is_err());
+    }
+
+    for size in 0..compressed.len() {
+        assert!(decompress(&compressed[..size]).is_err());
+        assert!(BlackHoleDecoder::new(&compressed[..size]).read_to_end(&mut Vec::new()).is_err());
+    }
+}
+
+#[test]
+fn encoder_and_decoder() {
+    let input = testdata(20 * 1024);
+
+    let mut encoder = BlackHoleEncoder::with_options(Vec::new(),BlackHoleOptions::builder().block_size(6 * 1024).build());
+
+    io::copy(&mut input.deref(),&mut encoder).unwrap();
+
+    let compressed = encoder.finish().unwrap();
+
+    assert_eq!(input,uncompress_with(&mut BlackHole::new(),&compressed));
+
+    let mut decoder = BlackHoleDecoder::new(BufReader::new(compressed.deref()));
+
+    let mut o = Vec::new();
+
+    decoder.read_to_end(&mut o).unwrap();
+
+    assert_eq!(input,o);
+
+    let compressed = compress_with(&mut BlackHole::new(),&input);
+
+    let mut decoder = BlackHoleDecoder::new(compressed.deref());
+
+    let mut o = Vec::new();
+
+    decoder.read_to_end(&mut o).unwrap();
+
+    assert_eq!(input,o);
+}
+
+struct FailingWriter<'a> {
+    writes:&'a Cell<usize>
+}
+impl<'a> Write for FailingWriter<'a> {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        self.writes.set(self.writes.get() + 1);
+
+        Err(io::Error::from(io::ErrorKind::BrokenPipe))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn encoder_and_decoder_failures() {
+    let input = testdata(8 * 1024);
+
+    let writes = Cell::new(0);
+
+    let mut encoder = BlackHoleEncoder::new(FailingWriter { writes: &writes });
+
+    encoder.write_all(&input).unwrap();
+
+    assert!(encoder.finish().is_err());
+    assert_eq!(1,writes.get());
+
+    let compressed = compress_with(&mut BlackHole::new(),&input);
+
+    assert_eq!(0,compressed[MAGIC.len() + 1] & FLAG_BLOCKS);
+
+    let options = BlackHoleOptions::builder().decode_limits(DecodeLimits::builder().max_output_size(1024).build()).build();
+
+    let e = BlackHoleDecoder::with_options(&compressed[..HEADER_SIZE],options).read_to_end(&mut Vec::new()).unwrap_err();
+
+    match e.into_inner().map(|e| e.downcast::<UnCompressionError>()) {
+        Some(Ok(e)) if matches!(*e,UnCompressionError::LimitExceeded(_)) => (),
+        r => panic!("unexpected result {:?}",r)
+    }
+
+    assert!(BlackHoleDecoder::new(&compressed[..compressed.len() / 2]).read_to_end(&mut Vec::new()).is_err());
+
+    let mut decoder = BlackHoleDecoder::new(ChunkedReader { data: &compressed, max_chunk: 7, state: 1 });
+
+    let mut o = Vec::new();
+
+    decoder.read_to_end(&mut o).unwrap();
+
+    assert_eq!(input,o);
+}
+
+#[test]
+fn decoder_reads_streams_without_blocks_lazily() {
+    let input = testdata(1024).repeat(128);
+
+    let compressed = compress_with(&mut BlackHole::new(),&input);
+
+    assert_eq!(0,compressed[MAGIC.len() + 1] & FLAG_BLOCKS);
+
+    let mut decoder = BlackHoleDecoder::new(&compressed[..]);
+
+    let mut o = vec![0; 1024];
+
+    decoder.read_exact(&mut o).unwrap();
+
+    assert_eq!(&input[..1024],&o[..]);
+    assert!(!decoder.get_ref().is_empty());
+
+    decoder.read_to_end(&mut o).unwrap();
+
+    assert_eq!(input,o);
+    assert!(decoder.get_ref().is_empty());
+}
+
+#[test]
+fn slice_compression_and_decompression() {
+    let input = testdata(8 * 1024);
+
+    let compressed = compress(&input).unwrap();
+
+    assert_eq!(input,decompress(&compressed).unwrap());
+    assert_eq!(input,decompress_with_limit(&compressed,input.len()).unwrap());
+
+    match decompress_with_limit(&compressed,input.len() - 1) {
+        Err(UnCompressionError::LimitExceeded(_)) => (),
+        r => panic!("unexpected result {:?}",r)
+    }
+}
+
+#[test]
+fn uncompression_with_decode_limits() {
+    let input = testdata(8 * 1024);
+
+    let compressed = compress(&input).unwrap();
+
+    let limits = [
+        DecodeLimits::builder().max_output_size(1024).build(),
+        DecodeLimits::builder().max_dictionary_entries(4).build(),
+        DecodeLimits::builder().max_word_size(1).build(),
+        DecodeLimits::builder().max_code_length(2).build()
+    ];
+
+    for l in limits.iter() {
+        let mut bh = BlackHole::with_options(BlackHoleOptions::builder().decode_limits(*l).build());
+
+        let mut i = compressed.deref();
+        let mut sr = StreamReader::new(&mut i);
+
+        let mut o = Vec::new();
+        let mut sw = StreamWriter::new(&mut o);
+
+        match bh.uncompression(&mut sr,&mut sw) {
+            Err(UnCompressionError::LimitExceeded(_)) => (),
+            r => panic!("unexpected result {:?}",r)
+        }
+    }
+
+    let mut bomb = MAGIC.to_vec();
+
+    bomb.extend_from_slice(&[VERSION,0]);
+    bomb.extend_from_slice(&(u64::MAX - 1).to_le_bytes());
+    bomb.extend_from_slice(&[0xFF; 9]);
+
+    match decompress_with_limit(&bomb,usize::MAX) {
+        Err(UnCompressionError::ReadError(ReadError::UnexpectedEofError)) => (),
+        r => panic!("unexpected result {:?}",r)
+    }
+
+    for r in [decompress(&bomb),decompress_with_limit(&bomb,1024)] {
+        match r {
+            Err(UnCompressionError::LimitExceeded(_)) => (),
+            r => panic!("unexpected result {:?}",r)
+        }
+    }
+}
+
+fn crafted_stream<F>(version:u8,size:u64,dictionary:F,payload:&[(u64,usize)]) -> Vec<u8> where F: Fn(&mut StreamWriter<'_,Vec<u8>>) {
+    let mut dic = Vec::new();
+
+    {
+        let mut sw = StreamWriter::new(&mut dic);
+
+        dictionary(&mut sw);
+
+        sw.pad_zeros().unwrap();
+        sw.flush().unwrap();
+    }
+
+    let mut r = Vec::new();
+
+    {
+        let mut sw = StreamWriter::new(&mut r);
+
+        Header::new(version,0,size).write(&mut sw).unwrap();
+
+        write_size(&mut sw,dic.len()).unwrap();
+        sw.write_bytes(&dic).unwrap();
+
+        for &(bits,len) in payload.iter() {
+            sw.write_bits(bits,len).unwrap();
+        }
+
+        sw.pad_zeros().unwrap();
+        sw.flush().unwrap();
+    }
+
+    r
+}
+
+fn uncompress_with_limits(limits:DecodeLimits,input:&[u8]) -> Result<Vec<u8>,UnCompressionError> {
+    let mut bh = BlackHole::with_options(BlackHoleOptions::builder().decode_limits(limits).build());
+
+    let mut i = input;
+    let mut sr = StreamReader::new(&mut i);
+
+    let mut o = Vec::new();
+
+    {
+        let mut sw = StreamWriter::new(&mut o);
+
+        bh.uncompression(&mut sr,&mut sw)?;
+    }
+
+    Ok(o)
+}
+
+#[test]
+fn uncompression_rejects_malformed_dictionaries() {
+    let unlimited = DecodeLimits::builder().max_output_size(u64::MAX)
+                                           .max_dictionary_entries(usize::MAX)
+                                           .max_code_length(usize::MAX)
+                                           .build();
+
+    let long_code = 32767;
+
+    let deep = crafted_stream(VERSION_EXPLICIT_CODES,1,|sw| {
+        write_size(sw,1).unwrap();
+        sw.write_bit(true).unwrap();
+        sw.write_bits((long_code & 0x7F) as u64,7).unwrap();
+        sw.write((long_code >> 7) as u8).unwrap();
+
+        for _ in 0..long_code {
+            sw.write_bit(true).unwrap();
+        }
+
+        write_size(sw,1).unwrap();
+        sw.write(b'a').unwrap();
+    },&[(0,1)].iter().cloned().chain((0..long_code).map(|_| (1,1))).collect::<Vec<(u64,usize)>>());
+
+    match decompress(&deep) {
+        Err(UnCompressionError::LimitExceeded(_)) => (),
+        r => panic!("unexpected result {:?}",r)
+    }
+
+    assert_eq!(b"a".to_vec(),uncompress_with_limits(unlimited,&deep).unwrap());
+
+    let zero_length_explicit = crafted_stream(VERSION_EXPLICIT_CODES,1 << 40,|sw| {
+        write_size(sw,1).unwrap();
+        sw.write_bits(0,8).unwrap();
+        write_size(sw,1).unwrap();
+        sw.write(b'a').unwrap();
+    },&[]);
+
+    let zero_length_canonical = crafted_stream(VERSION_CANONICAL_CODES,1 << 40,|sw| {
+        write_size(sw,1).unwrap();
+        write_size(sw,0).unwrap();
+        write_size(sw,1).unwrap();
+        write_size(sw,1).unwrap();
+        sw.write(b'a').unwrap();
+    },&[]);
+
+    for input in [&zero_length_explicit,&zero_length_canonical] {
+        match decompress(input) {
+            Err(UnCompressionError::LimitExceeded(_)) => (),
+            r => panic!("unexpected result {:?}",r.map(|v| v.len()))
+        }
+
+        match uncompress_with_limits(unlimited,input) {
+            Err(UnCompressionError::FormatError) => (),
+            r => panic!("unexpected result {:?}",r.map(|v| v.len()))
+        }
+    }
+
+    let huge_dictionary = crafted_stream(VERSION_CANONICAL_CODES,1,|sw| {
+        write_size(sw,1 << 40).unwrap();
+    },&[]);
+
+    match decompress(&huge_dictionary) {
+        Err(UnCompressionError::LimitExceeded(_)) => (),
+        r => panic!("unexpected result {:?}",r)
+    }
+
+    for version in [VERSION_EXPLICIT_CODES,VERSION_CANONICAL_CODES,VERSION_UNIFIED_ALPHABET] {
+        for data in [vec![7],vec![b'x'; 100]] {
+            let compressed = compress_with(&mut BlackHole::with_options(BlackHoleOptions::builder().version(version).build()),&data);
+
+            assert_eq!(data,decompress(&compressed).unwrap());
+        }
+    }
+}
+
+#[test]
+fn compression_is_deterministic() {
+    let input = testdata(16 * 1024);
+
+    let expected = compress(&input).unwrap();
+
+    for _ in 0..4 {
+        assert_eq!(expected,compress(&input).unwrap());
+    }
+
+    let words = (0..64u8).map(|b| (vec![b],Score::new(1,1))).collect::<Vec<(Vec<u8>,Score)>>();
+
+    let expected = HuffmanTree::new(words.clone());
+
+    for _ in 0..4 {
+        let tree = HuffmanTree::new(words.clone());
+
+        for (w,_) in words.iter() {
+            assert_eq!(expected.get_bits(w),tree.get_bits(w));
+        }
+    }
+}
+
+#[test]
+fn compression_with_each_format_version() {
+    let input = testdata(16 * 1024);
+
+    let mut sizes = Vec::new();
+
+    for version in [VERSION_EXPLICIT_CODES,VERSION_CANONICAL_CODES,VERSION_UNIFIED_ALPHABET].iter() {
+        let mut bh = BlackHole::with_options(BlackHoleOptions::builder().version(*version).build());
+
+        let compressed = compress_with(&mut bh,&input);
+
+        assert_eq!(compressed[MAGIC.len()],*version);
+        assert_eq!(input,decompress(&compressed).unwrap());
+
+        sizes.push(compressed.len());
+    }
+
+    assert!(sizes[1] < sizes[0]);
+    assert!(sizes[2] < sizes[1]);
+}
+
+#[test]
+fn canonical_huffman_codes() {
+    let words = vec![
+        (b"a".to_vec(),Score::new(1,10)),
+        (b"b".to_vec(),Score::new(1,1)),
+        (b"c".to_vec(),Score::new(1,1)),
+        (b"d".to_vec(),Score::new(1,5)),
+        (b"e".to_vec(),Score::new(1,2))
+    ];
+
+    let tree = HuffmanTree::canonical(words).unwrap();
+
+    let lengths = tree.code_lengths().into_iter().map(|(w,l)| (w.clone(),l)).collect::<Vec<(Vec<u8>,usize)>>();
+
+    let rebuilt = HuffmanTree::from_lengths(lengths).unwrap();
+
+    for w in [b"a",b"b",b"c",b"d",b"e"].iter() {
+        assert_eq!(tree.get_bits(&w.to_vec()),rebuilt.get_bits(&w.to_vec()));
+    }
+
+    assert!(HuffmanTree::from_lengths(vec![(b"a".to_vec(),1),(b"b".to_vec(),1),(b"c".to_vec(),1)]).is_err());
+}
+
+#[test]
+fn length_limited_huffman_codes() {
+    let mut counts = vec![1usize,1];
+
+    while counts.len() < 32 {
+        let n = counts[counts.len() - 1] + counts[counts.len() - 2];
+        counts.push(n);
+    }
+
+    let words = counts.iter().enumerate().map(|(i,&c)| (vec![i as u8],Score::new(1,c))).collect::<Vec<(Vec<u8>,Score)>>();
+
+    assert!(HuffmanTree::new(words.clone()).code_lengths().iter().any(|&(_,l)| l > 8));
+
+    let tree = HuffmanTree::length_limited(words.clone(),8).unwrap();
+
+    assert_eq!(tree.len(),words.len());
+    assert!(tree.code_lengths().iter().all(|&(_,l)| l <= 8));
+
+    let kraft = tree.code_lengths().iter().fold(0u64,|acc,&(_,l)| acc + (1 << (8 - l)));
+
+    assert_eq!(kraft,1 << 8);
+
+    assert!(HuffmanTree::length_limited(words,4).is_err());
+
+    let input = testdata(16 * 1024);
+
+    let mut bh = BlackHole::with_options(BlackHoleOptions::builder().max_code_length(9).build());
+
+    let compressed = compress_with(&mut bh,&input);
+
+    assert_eq!(input,decompress(&compressed).unwrap());
+}
+
+#[test]
+fn table_decoding_matches_tree_decoding() {
+    let mut counts = vec![1usize,1];
+
+    while counts.len() < 22 {
+        let n = counts[counts.len() - 1] + counts[counts.len() - 2];
+        counts.push(n);
+    }
+
+    let words = counts.iter().enumerate().map(|(i,&c)| (vec![i as u8; i + 1],Score::new(i + 1,c))).collect::<Vec<(Vec<u8>,Score)>>();
+
+    let tree = HuffmanTree::canonical(words.clone()).unwrap();
+    let table = HuffmanDecodeTable::new(&tree).unwrap();
+
+    assert!(tree.code_lengths().iter().any(|&(_,l)| l > PRIMARY_TABLE_BITS));
+
+    let seq = (0..1000).map(|i| words[(i * 7919) % words.len()].0.clone()).collect::<Vec<Vec<u8>>>();
+
+    let mut encoded = Vec::new();
+
+    {
+        let mut sw = StreamWriter::new(&mut encoded);
+
+        for w in seq.iter() {
+            tree.write(&mut sw,w.clone()).unwrap();
+        }
+
+        sw.pad_zeros().unwrap();
+        sw.flush().unwrap();
+    }
+
+    let mut i = encoded.deref();
+    let mut tree_reader = StreamReader::new(&mut i);
+
+    let mut j = encoded.deref();
+    let mut table_reader = StreamReader::new(&mut j);
+
+    for w in seq.iter() {
+        assert_eq!(w,tree.find_word(&mut tree_reader).unwrap());
+        assert_eq!(w,table.find_word(&mut table_reader).unwrap());
+    }
+}
+
+fn next_random(state:&mut u64) -> u64 {
+    *state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    *state >> 33
+}
+
+fn bits_at(data:&[u8],position:u64,size:usize) -> u64 {
+    (0..size).fold(0u64,|acc,i| {
+        let p = position + i as u64;
+
+        acc | ((((data[(p / 8) as usize] >> (p % 8)) & 1) as u64) << i)
+    })
+}
+
+#[test]
+fn stream_bit_buffer_round_trip() {
+    let mut state = 0x9E3779B97F4A7C15;
+
+    let fields = (0..2000).map(|_| {
+        let len = (next_random(&mut state) % 65) as usize;
+        let value = next_random(&mut state);
+
+        (if len < 64 { value & ((1u64 << len) - 1) } else { value },len)
+    }).collect::<Vec<(u64,usize)>>();
+
+    let mut data = Vec::new();
+
+    {
+        let mut sw = StreamWriter::new(&mut data);
+
+        for &(value,len) in fields.iter() {
+            sw.write_bits(value,len).unwrap();
+        }
+
+        sw.write_bits(0b101,3).unwrap();
+        sw.pad_zeros().unwrap();
+        sw.flush().unwrap();
+    }
+
+    let total = fields.iter().map(|&(_,len)| len).sum::<usize>() + 3;
+
+    assert_eq!(total.div_ceil(8),data.len());
+
+    let mut r = data.deref();
+    let mut sr = StreamReader::new(&mut r);
+
+    for &(value,len) in fields.iter() {
+        if len > MAX_PEEK_BITS {
+            let low = sr.read_bits(32).unwrap();
+            let high = sr.read_bits(len - 32).unwrap();
+
+            assert_eq!(value,low | (high << 32));
+        } else {
+            assert_eq!(value,sr.peek_bits(len).unwrap());
+
+            sr.consume(len).unwrap();
+        }
+    }
+
+    let padding = data.len() * 8 - total;
+
+    assert_eq!(0b101,sr.peek_bits(MAX_PEEK_BITS).unwrap());
+    assert!(sr.peek_bits(MAX_PEEK_BITS + 1).is_err());
+    assert!(sr.consume(3 + padding + 1).is_err());
+
+    sr.consume(3 + padding).unwrap();
+
+    assert_eq!(0,sr.peek_bits(8).unwrap());
+    assert!(sr.read_bits(1).is_err());
+}
+
+#[test]
+fn stream_reader_bit_seeking() {
+    let mut state = 0x2545F4914F6CDD1D;
+
+    let data = (0..4096).map(|_| next_random(&mut state) as u8).collect::<Vec<u8>>();
+    let total = data.len() as u64 * 8;
+
+    for _ in 0..64 {
+        let mut r = data.deref();
+        let mut sr = StreamReader::new(&mut r);
+
+        let mut position = 0u64;
+
+        loop {
+            assert_eq!(position,sr.bit_position());
+
+            match next_random(&mut state) % 3 {
+                0 => {
+                    let size = 1 + (next_random(&mut state) % 57) as usize;
+
+                    if position + size as u64 > total {
+                        assert!(sr.read_bits(size).is_err());
+                        break;
+                    }
+
+                    assert_eq!(bits_at(&data,position,size),sr.read_bits(size).unwrap());
+
+                    position += size as u64;
+                },
+                1 => {
+                    let size = (next_random(&mut state) % 3000) as usize;
+
+                    if position + size as u64 > total {
+                        assert!(sr.skip_bits(size).is_err());
+                        break;
+                    }
+
+                    sr.skip_bits(size).unwrap();
+
+                    position += size as u64;
+                },
+                _ => {
+                    sr.align_to_byte();
+
+                    position = position.div_ceil(8) * 8;
+                }
+            }
+        }
+    }
+
+    let mut o = Vec::new();
+
+    {
+        let mut sw = StreamWriter::new(&mut o);
+
+        sw.write_bits(0b101,3).unwrap();
+        sw.pad_zeros().unwrap();
+        sw.write_u32(0xDEADBEEF).unwrap();
+        sw.flush().unwrap();
+    }
+
+    let mut r = o.deref();
+    let mut sr = StreamReader::new(&mut r);
+
+    assert_eq!(0b101,sr.read_bits(3).unwrap());
+
+    sr.align_to_byte();
+
+    assert_eq!(8,sr.bit_position());
+    assert_eq!(0xDEADBEEF,sr.read_u32().unwrap());
+    assert_eq!(40,sr.bit_position());
+
+    let mut r = o.deref();
+    let mut sr = StreamReader::new(&mut r);
+
+    assert_eq!(0b101,sr.read_bits(3).unwrap());
+
+    #[allow(deprecated)]
+    sr.skip_surplus_bits();
+
+    assert_eq!(0xDEADBEEF,sr.read_u32().unwrap());
+}
+
+struct ChunkedReader<'a> {
+    data:&'a [u8],
+    max_chunk:usize,
+    state:u64
+}
+impl<'a> Read for ChunkedReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let r = next_random(&mut self.state);
+
+        if r % 7 == 6 {
+            return Err(io::Error::from(io::ErrorKind::Interrupted));
+        }
+
+        let size = (1 + r as usize % self.max_chunk).min(buf.len()).min(self.data.len());
+
+        buf[..size].copy_from_slice(&self.data[..size]);
+
+        self.data = &self.data[size..];
+
+        Ok(size)
+    }
+}
+
+#[test]
+fn stream_reader_with_partial_reads() {
+    let mut state = 0x9E3779B97F4A7C15;
+
+    let data = (0..2048).map(|_| next_random(&mut state) as u8).collect::<Vec<u8>>();
+
+    for &max_chunk in [1,3,255,1000].iter() {
+        let mut r = ChunkedReader { data: &data, max_chunk, state };
+        let mut sr = StreamReader::new(&mut r);
+
+        let mut position = 0u64;
+
+        assert!(!sr.is_eof().unwrap());
+
+        while position < data.len() as u64 * 8 {
+            let size = (1 + next_random(&mut state) % 57).min(data.len() as u64 * 8 - position) as usize;
+
+            if next_random(&mut state) & 1 == 0 {
+                assert_eq!(bits_at(&data,position,size),sr.read_bits(size).unwrap());
+            } else {
+                sr.skip_bits(size).unwrap();
+            }
+
+            position += size as u64;
+
+            assert_eq!(position,sr.bit_position());
+            assert!(sr.remaining_in_buffer() <= 256 + 8);
+        }
+
+        assert_eq!(0,sr.remaining_in_buffer());
+        assert!(sr.is_eof().unwrap());
+        assert!(sr.read_once().unwrap().is_none());
+        assert!(sr.read_bits(1).is_err());
+    }
+
+    let input = testdata(32 * 1024);
+    let compressed = compress(&input).unwrap();
+
+    for &max_chunk in [1,7].iter() {
+        let mut r = ChunkedReader { data: &compressed, max_chunk, state };
+        let mut sr = StreamReader::new(&mut r);
+
+        let mut o = Vec::new();
+
+        {
+            let mut sw = StreamWriter::new(&mut o);
+
+            BlackHole::new().uncompression(&mut sr,&mut sw).unwrap();
+            sw.flush().unwrap();
+        }
+
+        assert_eq!(input,o);
+    }
+}
+
+#[test]
+fn random_access_with_seek_index() {
+    let input = testdata(48 * 1024 + 123);
+
+    let options = BlackHoleOptions::builder().block_size(4 * 1024).seek_index(true).build();
+
+    let compressed = compress_with(&mut BlackHole::with_options(options.clone()),&input);
+
+    let mut parallel = Vec::new();
+
+    {
+        let mut i = input.deref();
+        let mut sr = StreamReader::new(&mut i);
+        let mut sw = StreamWriter::new(&mut parallel);
+
+        BlackHole::with_options(options.clone()).compress_parallel(&mut sr,&mut sw).unwrap();
+    }
+
+    let mut encoder = BlackHoleEncoder::with_options(Vec::new(),options.clone());
+
+    for chunk in input.chunks(1000) {
+        encoder.write_all(chunk).unwrap();
+    }
+
+    let encoded = encoder.finish().unwrap();
+
+    assert_eq!(compressed,parallel);
+
+    for c in [&compressed[..],&encoded[..]].iter() {
+        assert_eq!(input,decompress(c).unwrap());
+
+        let mut decoded = Vec::new();
+
+        BlackHoleDecoder::new(*c).read_to_end(&mut decoded).unwrap();
+
+        assert_eq!(input,decoded);
+
+        let mut reader = BlackHoleSeekableReader::new(Cursor::new(*c)).unwrap();
+
+        assert_eq!(input.len() as u64,reader.len());
+        assert_eq!(13,reader.index().len());
+
+        let mut state = 0x853C49E6748FEA9B;
+
+        for _ in 0..200 {
+            let start = (next_random(&mut state) as usize) % (input.len() + 10);
+            let size = (next_random(&mut state) as usize) % 10000;
+
+            assert_eq!(start as u64,reader.seek(SeekFrom::Start(start as u64)).unwrap());
+
+            let mut buf = Vec::new();
+
+            (&mut reader).take(size as u64).read_to_end(&mut buf).unwrap();
+
+            let end = (start + size).min(input.len()).max(start.min(input.len()));
+
+            assert_eq!(&input[start.min(input.len())..end],&buf[..]);
+        }
+
+        assert_eq!(input.len() as u64 - 10,reader.seek(SeekFrom::End(-10)).unwrap());
+        assert_eq!(input.len() as u64 - 15,reader.seek(SeekFrom::Current(-5)).unwrap());
+        assert!(reader.seek(SeekFrom::Current(-(input.len() as i64))).is_err());
+
+        let mut buf = Vec::new();
+
+        reader.read_to_end(&mut buf).unwrap();
+
+        assert_eq!(&input[(input.len() - 15)..],&buf[..]);
+    }
+
+    let plain = compress_with(&mut BlackHole::with_options(BlackHoleOptions::builder().block_size(4 * 1024).build()),&input);
+
+    assert!(BlackHoleSeekableReader::new(Cursor::new(plain.deref())).is_err());
+
+    let mut corrupted = compressed.clone();
+    let len = corrupted.len();
+
+    corrupted[len - 1] ^= 0xFF;
+
+    assert!(BlackHoleSeekableReader::new(Cursor::new(corrupted.deref())).is_err());
+}
+
+#[test]
+fn compression_with_shared_dictionary() {
+    let corpus = testdata(64 * 1024);
+
+    let samples = corpus.chunks(512).take(100).collect::<Vec<&[u8]>>();
+
+    let dictionary = Dictionary::train(&samples,&BlackHoleOptions::new()).unwrap();
+
+    assert!(!dictionary.is_empty());
+
+    let bytes = dictionary.to_bytes().unwrap();
+    let restored = Dictionary::from_bytes(&bytes).unwrap();
+
+    assert_eq!(dictionary.id(),restored.id());
+    assert_eq!(dictionary.words(),restored.words());
+
+    let mut corrupted = bytes.clone();
+
+    corrupted[10] ^= 0xFF;
+
+    assert!(Dictionary::from_bytes(&corrupted).is_err());
+
+    let options = BlackHoleOptions::builder().dictionary(Arc::new(dictionary)).build();
+    let restored_options = BlackHoleOptions::builder().dictionary(Arc::new(restored)).build();
+
+    for message in corpus[(60 * 1024)..].chunks(700) {
+        let with_dictionary = compress_with(&mut BlackHole::with_options(options.clone()),message);
+        let without_dictionary = compress(message).unwrap();
+
+        assert!(with_dictionary.len() < without_dictionary.len());
+
+        assert_eq!(message,&uncompress_with(&mut BlackHole::with_options(restored_options.clone()),&with_dictionary)[..]);
+
+        match decompress(&with_dictionary) {
+            Err(UnCompressionError::DictionaryMismatch(id)) => assert_eq!(id,restored_options.dictionary().unwrap().id()),
+            r => panic!("unexpected result: {:?}",r.map(|v| v.len()))
+        }
+    }
+
+    let other = Dictionary::train(&[&corpus[..1024]],&BlackHoleOptions::new()).unwrap();
+    let other_options = BlackHoleOptions::builder().dictionary(Arc::new(other)).build();
+
+    let compressed = compress_with(&mut BlackHole::with_options(options.clone()),&corpus[..5000]);
+
+    let mut i = compressed.deref();
+    let mut sr = StreamReader::new(&mut i);
+    let mut o = Vec::new();
+    let mut sw = StreamWriter::new(&mut o);
+
+    assert!(BlackHole::with_options(other_options).uncompression(&mut sr,&mut sw).is_err());
+}
+
+#[test]
+fn shared_dictionary_with_huge_counts() {
+    let words = [b"alpha".to_vec(),b"bravo".to_vec(),b"charlie".to_vec(),b"delta".to_vec()].into_iter()
+                                                                                        .map(|w| (w,(1 << 62) - 1))
+                                                                                        .collect::<BTreeMap<Vec<u8>,usize>>();
+
+    let bytes = Dictionary::new(words).unwrap().to_bytes().unwrap();
+    let dictionary = Arc::new(Dictionary::from_bytes(&bytes).unwrap());
+
+    assert_eq!(4,dictionary.len());
+
+    let input = b"alpha bravo charlie delta alpha bravo charlie delta".to_vec();
+
+    for version in [VERSION_CANONICAL_CODES,VERSION_UNIFIED_ALPHABET] {
+        let options = BlackHoleOptions::builder().version(version).dictionary(dictionary.clone()).build();
+
+        let compressed = compress_with(&mut BlackHole::with_options(options.clone()),&input);
+
+        assert_eq!(input,uncompress_with(&mut BlackHole::with_options(options),&compressed));
+    }
+}
+
+#[test]
+fn dictionary_training_with_size_budget() {
+    let corpus = testdata(32 * 1024);
+
+    let samples = corpus.chunks(8 * 1024).collect::<Vec<&[u8]>>();
+    let options = BlackHoleOptions::builder().min_count(16).build();
+
+    let dictionary = Dictionary::train_with_max_size(&samples,&options,2048).unwrap();
+
+    assert!(dictionary.to_bytes().unwrap().len() <= 2048);
+    assert!(dictionary.words().iter().all(|(w,&c)| w.len() == 1 || c >= 16));
+    assert!(dictionary.words().keys().any(|w| w.len() > 1));
+
+    let statistics = Dictionary::statistics(&samples,&options).unwrap();
+
+    assert_eq!(statistics.iter().map(|(w,&c)| w.len() * c).sum::<usize>(),corpus.len());
+
+    let samples = [b"0123456789"; 16];
+    let statistics = Dictionary::statistics(&samples,&BlackHoleOptions::builder().min_count(2).build()).unwrap();
+
+    assert!(statistics.keys().all(|w| w.len() == 1));
+    assert_eq!(16,statistics[&b"0".to_vec()]);
+
+    let literals = (0..=255u8).map(|b| (vec![b],1000)).collect::<BTreeMap<Vec<u8>,usize>>();
+
+    for max_size in [16,64,256] {
+        assert!(Dictionary::from_statistics(&literals,max_size,16).unwrap().to_bytes().unwrap().len() <= max_size);
+    }
+
+    let dir = std::env::temp_dir().join(format!("blackhole-train-{}",std::process::id()));
+
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut files = Vec::new();
+
+    for (i,sample) in corpus.chunks(8 * 1024).enumerate() {
+        let path = dir.join(format!("sample{}",i));
+
+        std::fs::write(&path,sample).unwrap();
+
+        files.push(path);
+    }
+
+    let output = dir.join("dict.bhd");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_blackhole"))
+                         .arg("train").arg("-o").arg(&output).arg("--max-size").arg("2048").arg("--min-count").arg("16")
+                         .args(&files)
+                         .status().unwrap();
+
+    assert!(status.success());
+
+    let trained = Dictionary::from_bytes(&std::fs::read(&output).unwrap()).unwrap();
+
+    assert_eq!(dictionary.id(),trained.id());
+
+    let status = Command::new(env!("CARGO_BIN_EXE_blackhole")).arg("train").arg("--unknown").status().unwrap();
+
+    assert!(!status.success());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn command_line_compress_and_decompress() {
+    let input = testdata(16 * 1024);
+
+    let dir = std::env::temp_dir().join(format!("blackhole-cli-{}",std::process::id()));
+
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let path = dir.join("data");
+    let compressed_path = dir.join("data.bh");
+
+    std::fs::write(&path,&input).unwrap();
+
+    let blackhole = || Command::new(env!("CARGO_BIN_EXE_blackhole"));
+
+    assert!(blackhole().arg("compress").arg("-k").arg(&path).status().unwrap().success());
+    assert!(path.exists());
+    assert_eq!(input,decompress(&std::fs::read(&compressed_path).unwrap()).unwrap());
+
+    assert!(!blackhole().arg("compress").arg(&path).stderr(Stdio::null()).status().unwrap().success());
+    assert!(blackhole().arg("compress").arg("-f").arg(&path).status().unwrap().success());
+    assert!(!path.exists());
+
+    assert!(blackhole().arg("decompress").arg(&compressed_path).status().unwrap().success());
+    assert!(!compressed_path.exists());
+    assert_eq!(input,std::fs::read(&path).unwrap());
+
+    assert!(!blackhole().arg("decompress").arg(&path).stderr(Stdio::null()).status().unwrap().success());
+
+    let mut child = blackhole().arg("compress").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+
+    child.stdin.take().unwrap().write_all(&input).unwrap();
+
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(input,decompress(&output.stdout).unwrap());
+
+    let output = blackhole().arg("decompress").arg("-c").arg("-").stdin(std::fs::File::open(&path).unwrap()).output().unwrap();
+
+    assert!(!output.status.success());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn inspect_compressed_stream() {
+    let input = testdata(20 * 1024 + 7);
+
+    for options in [BlackHoleOptions::new(),
+                    BlackHoleOptions::builder().block_size(4 * 1024).build(),
+                    BlackHoleOptions::builder().block_size(4 * 1024).seek_index(true).content_checksum(false).build()].iter() {
+        let compressed = compress_with(&mut BlackHole::with_options(options.clone()),&input);
+
+        let mut i = compressed.deref();
+        let mut sr = StreamReader::new(&mut i);
+
+        let info = BlackHole::new().inspect(&mut sr).unwrap();
+
+        assert_eq!(compressed.len() as u64,info.compressed_size());
+        assert_eq!(input.len() as u64,info.size());
+        assert_eq!(info.blocks().iter().filter(|b| !b.is_stored()).map(|b| b.size()).sum::<usize>(),info.literal_tokens() + info.word_bytes());
+        assert_eq!(info.blocks().len(),if options.block_size().is_some() { 6 } else { 1 });
+        assert_eq!(options.seek_index(),info.index_size() > 0);
+
+        for block in info.blocks().iter().filter(|b| !b.is_stored()) {
+            assert!(!block.words().is_empty());
+            assert!(block.dictionary_size() > 0 && block.payload_size() > 0);
+        }
+    }
+
+    for version in [VERSION_EXPLICIT_CODES,VERSION_CANONICAL_CODES,VERSION_UNIFIED_ALPHABET] {
+        let compressed = compress_with(&mut BlackHole::with_options(BlackHoleOptions::builder().version(version).build()),&input);
+
+        let mut i = compressed.deref();
+        let info = BlackHole::new().inspect(&mut StreamReader::new(&mut i)).unwrap();
+
+        assert_eq!(input.len(),info.literal_tokens() + info.word_bytes());
+        assert!(info.word_bytes() >= 2 * info.word_tokens());
+    }
+
+    let dir = std::env::temp_dir().join(format!("blackhole-inspect-{}",std::process::id()));
+
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let path = dir.join("data.bh");
+
+    std::fs::write(&path,compress(&input).unwrap()).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_blackhole")).arg("inspect").arg(&path).output().unwrap();
+
+    assert!(output.status.success());
+
+    let text = String::from_utf8(output.stdout).unwrap();
+
+    assert!(text.contains(&format!("original size:     {} bytes",input.len())));
+    assert!(text.contains("dictionary entries:"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn suffix_array_and_lcp() {
+    let mut state = 0x4F1BBCDCBFA53E0B;
+
+    let inputs = [Vec::new(),
+                      b"a".to_vec(),
+                      b"banana".to_vec(),
+                      b"mississippi".to_vec(),
+                      vec![0u8; 300],
+                      (0..2000).map(|_| (next_random(&mut state) % 3) as u8).collect::<Vec<u8>>(),
+                      (0..2000).map(|_| next_random(&mut state) as u8).collect::<Vec<u8>>(),
+                      testdata(4096)];
+
+    for data in inputs.iter() {
+        let sa = suffix_array(data);
+
+        let mut expected = (0..data.len()).collect::<Vec<usize>>();
+
+        expected.sort_by(|&l,&r| data[l..].cmp(&data[r..]));
+
+        assert_eq!(expected,sa);
+
+        let lcp = lcp_array(data,&sa);
+
+        for (i,&h) in lcp.iter().enumerate() {
+            let (l,r) = (&data[sa[i]..],&data[sa[i + 1]..]);
+
+            assert_eq!(l.iter().zip(r.iter()).take_while(|(a,b)| a == b).count(),h);
+        }
+    }
+}
+
+#[test]
+fn suffix_array_analysis_matches_ngram_analysis() {
+    let mut state = 0xDA942042E4DD58B5;
+
+    let cases = [(testdata(6 * 1024),8,64),
+                     (testdata(6 * 1024),3,5),
+                     (testdata(500),1,64),
+                     (testdata(300),0,16),
+                     ((0..3000).map(|_| b"ab"[(next_random(&mut state) % 2) as usize]).collect::<Vec<u8>>(),8,64),
+                     (vec![b'a'; 1000],8,64),
+                     (vec![b'a'; 1000],2,1),
+                     (vec![0; 64 * 1024],8,64),
+                     (b"GET /index.html 200\n".iter().cycle().take(16 * 1024).cloned().collect::<Vec<u8>>(),8,64),
+                     (Vec::new(),8,64),
+                     (b"x".to_vec(),1,64)];
+
+    for (data,min_count,max_word_size) in cases.into_iter() {
+        let analyse = |method| {
+            let options = BlackHoleOptions::builder().min_count(min_count).max_word_size(max_word_size).analysis_method(method).build();
+
+            BlackHole::with_options(options).analysis_data(&data).unwrap().iter().map(|w| {
+                (w.word().to_vec(),w.score(),w.positions().clone())
+            }).collect::<Vec<_>>()
+        };
+
+        assert_eq!(analyse(AnalysisMethod::NGram),analyse(AnalysisMethod::SuffixArray));
+    }
+}
+
+#[test]
+fn optimal_parsing_round_trip() {
+    let data = testdata(32 * 1024);
+
+    let greedy = compress(&data).unwrap();
+
+    for iterations in [1,2,4] {
+        let options = BlackHoleOptions::builder().optimal_parse_iterations(iterations).build();
+        let optimal = compress_with(&mut BlackHole::with_options(options),&data);
+
+        assert!(optimal.len() <= greedy.len(),"iterations {}: {} > {}",iterations,optimal.len(),greedy.len());
+        assert_eq!(data,decompress(&optimal).unwrap());
+    }
+
+    let options = BlackHoleOptions::builder().optimal_parse_iterations(2).max_code_length(12).version(2).build();
+
+    for size in [0,1,2,100,5000] {
+        let data = testdata(size);
+        let compressed = compress_with(&mut BlackHole::with_options(options.clone()),&data);
+
+        assert_eq!(data,decompress(&compressed).unwrap());
+    }
+}
+
+#[test]
+fn estimated_bits_score_model() {
+    let mut data = Vec::new();
+
+    File::open("testdata/legal_moves.rs").unwrap().read_to_end(&mut data).unwrap();
+
+    data.truncate(64 * 1024);
+
+    let context = ScoreContext::new(&data);
+
+    assert_eq!(data.len(),context.size());
+    assert!(context.literal_bits(b' ') < context.literal_bits(b'q'));
+    assert_eq!(8.0,context.literal_bits(0));
+    assert_eq!(0.0,context.flag_bits());
+
+    let flagged = ScoreContext::with_version(&data,VERSION_CANONICAL_CODES);
+
+    assert_eq!(9.0,flagged.literal_bits(0));
+    assert_eq!(context.literal_bits(b' ') + 1.0,flagged.literal_bits(b' '));
+    assert_eq!(EstimatedBitsModel.code_bits(10,&context) + 1.0,EstimatedBitsModel.code_bits(10,&flagged));
+
+    let model = EstimatedBitsModel;
+
+    assert_eq!(0,model.value(b" ",1000,&context));
+    assert_eq!(0,model.value(b"fn",1,&context));
+    assert!(model.value(b"legal_moves",40,&context) > model.value(b"legal_moves",4,&context));
+    assert!(model.value(b"legal_moves",10,&context) > model.value(b"le",10,&context));
+
+    assert_eq!(Score::new(5,3).value(),DefaultScoreModel.value(b"abcde",3,&context));
+    assert_eq!(Score::with_model(b"abcde",3,&context,&model).value(),model.value(b"abcde",3,&context));
+
+    let options = BlackHoleOptions::builder().score_model(Arc::new(model)).build();
+
+    let default = compress(&data).unwrap();
+    let estimated = compress_with(&mut BlackHole::with_options(options.clone()),&data);
+
+    assert!(estimated.len() < default.len());
+    assert_eq!(data,decompress(&estimated).unwrap());
+
+    for size in [0,1,2,100,5000] {
+        let data = testdata(size);
+        let compressed = compress_with(&mut BlackHole::with_options(options.clone()),&data);
+
+        assert_eq!(data,decompress(&compressed).unwrap());
+    }
+}
+
+#[test]
+fn unified_alphabet_without_flag_bits() {
+    let options = BlackHoleOptions::builder().version(VERSION_UNIFIED_ALPHABET).build();
+
+    for data in [Vec::new(),vec![7],vec![b'a'; 1000],b"abababababcabababab".to_vec(),testdata(5000)] {
+        for options in [options.clone(),BlackHoleOptions::builder().version(VERSION_UNIFIED_ALPHABET).max_code_length(9).block_size(700).build()] {
+            let compressed = compress_with(&mut BlackHole::with_options(options),&data);
+
+            assert_eq!(compressed[MAGIC.len()],VERSION_UNIFIED_ALPHABET);
+            assert_eq!(data,decompress(&compressed).unwrap());
+        }
+    }
+
+    let data = testdata(16 * 1024);
+
+    let compressed = compress_with(&mut BlackHole::with_options(options.clone()),&data);
+
+    let mut reader = &compressed[..];
+    let info = BlackHole::new().inspect(&mut StreamReader::new(&mut reader)).unwrap();
+
+    assert_eq!(data.len(),info.literal_tokens() + info.word_bytes());
+
+    let samples = data.chunks(512).take(16).collect::<Vec<&[u8]>>();
+    let dictionary = Arc::new(Dictionary::train(&samples,&BlackHoleOptions::new()).unwrap());
+
+    for version in [VERSION_CANONICAL_CODES,VERSION_UNIFIED_ALPHABET] {
+        let options = BlackHoleOptions::builder().version(version).dictionary(dictionary.clone()).build();
+
+        let mut message = data[(12 * 1024)..(13 * 1024)].to_vec();
+
+        message.extend_from_slice(&[0,255,1,254]);
+
+        let compressed = compress_with(&mut BlackHole::with_options(options.clone()),&message);
+
+        assert_eq!(message,uncompress_with(&mut BlackHole::with_options(options),&compressed));
+    }
+}
+
+#[test]
+fn unified_alphabet_with_max_dictionary_size() {
+    let data = testdata(16 * 1024);
+
+    let literals = data.iter().collect::<std::collections::BTreeSet<&u8>>().len();
+
+    for max in [0,literals,literals + 16,usize::MAX] {
+        for optimal_parse_iterations in [0,2] {
+            let options = BlackHoleOptions::builder().version(VERSION_UNIFIED_ALPHABET)
+                                                     .max_dictionary_size(max)
+                                                     .optimal_parse_iterations(optimal_parse_iterations)
+                                                     .build();
+
+            let compressed = compress_with(&mut BlackHole::with_options(options),&data);
+
+            let mut i = compressed.deref();
+            let info = BlackHole::new().inspect(&mut StreamReader::new(&mut i)).unwrap();
+
+            assert!(info.blocks().iter().all(|b| b.words().len() <= max.max(literals)));
+            assert_eq!(data,decompress(&compressed).unwrap());
+        }
+    }
+}
+
+#[test]
+fn stored_blocks_for_incompressible_data() {
+    let mut state = 0x5eed;
+
+    let random = (0..(20 * 1024)).map(|_| next_random(&mut state) as u8).collect::<Vec<u8>>();
+
+    for size in [0,1,100,random.len()] {
+        let data = &random[..size];
+
+        let compressed = compress(data).unwrap();
+
+        assert!(compressed.len() <= HEADER_SIZE + data.len() + 1 + 2 * encoded_size_len(data.len() + 4) + 4 + 1);
+        assert_eq!(data,&decompress(&compressed).unwrap()[..]);
+    }
+
+    let mut input = testdata(16 * 1024);
+
+    input.extend_from_slice(&random[..(8 * 1024)]);
+    input.extend_from_slice(&testdata(16 * 1024));
+
+    let options = BlackHoleOptions::builder().block_size(8 * 1024).threads(2).seek_index(true).build();
+
+    let compressed = compress_with(&mut BlackHole::with_options(options.clone()),&input);
+
+    let mut reader = compressed.deref();
+    let info = BlackHole::new().inspect(&mut StreamReader::new(&mut reader)).unwrap();
+
+    assert_eq!(vec![false,false,true,false,false],info.blocks().iter().map(|b| b.is_stored()).collect::<Vec<bool>>());
+    assert_eq!(input,decompress(&compressed).unwrap());
+
+    let mut i = input.deref();
+    let mut sr = StreamReader::new(&mut i);
+    let mut parallel = Vec::new();
+    let mut sw = StreamWriter::new(&mut parallel);
+
+    BlackHole::with_options(options.clone()).compress_parallel(&mut sr,&mut sw).unwrap();
+
+    assert_eq!(compressed,parallel);
+
+    let mut i = compressed.deref();
+    let mut sr = StreamReader::new(&mut i);
+    let mut o = Vec::new();
+    let mut sw = StreamWriter::new(&mut o);
+
+    BlackHole::with_options(options).decompress_parallel(&mut sr,&mut sw).unwrap();
+
+    assert_eq!(input,o);
+
+    let mut reader = BlackHoleSeekableReader::new(Cursor::new(&compressed)).unwrap();
+    let mut o = vec![0; 100];
+
+    reader.seek(SeekFrom::Start(17 * 1024)).unwrap();
+    reader.read_exact(&mut o).unwrap();
+
+    assert_eq!(&input[(17 * 1024)..(17 * 1024 + 100)],&o[..]);
+
+    let stored = compress(&random).unwrap();
+    let mut corrupted = stored.clone();
+
+    corrupted[HEADER_SIZE + 10] ^= 0xFF;
+
+    match decompress(&corrupted) {
+        Err(UnCompressionError::ChecksumMismatch { .. }) => (),
+        r => panic!("unexpected result: {:?}",r.map(|v| v.len()))
+    }
+}